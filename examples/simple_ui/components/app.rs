@@ -1,4 +1,5 @@
 use ggez::graphics::Color;
+use react_rs::react;
 use ui::*;
 
 pub struct App;
@@ -12,46 +13,15 @@ impl Component<WidgetElement> for App {
     }
 
     fn render(&self, _ctx: RenderContext<'_, App>) -> Element {
-        Element::new_host(
-            ListBoxElt {
-                ..Default::default()
-            },
-            vec![
-                Element::new_host(
-                    TextLabelElt {
-                        text: "Line 1".to_owned(),
-                        ..Default::default()
-                    },
-                    vec![],
-                ),
-                Element::new_host(
-                    TextLabelElt {
-                        text: "Line 2".to_owned(),
-                        ..Default::default()
-                    },
-                    vec![],
-                ),
-                Element::new_host(
-                    TextLabelElt {
-                        text: "Line 3".to_owned(),
-                        ..Default::default()
-                    },
-                    vec![],
-                ),
-                Element::new_host(
-                    ListBoxElt {
-                        background_color: Color::from_rgb(32, 32, 96),
-                        ..Default::default()
-                    },
-                    vec![Element::new_host(
-                        TextLabelElt {
-                            text: "the quick brown fox jumps over the lazy dog".to_owned(),
-                            ..Default::default()
-                        },
-                        vec![],
-                    )],
-                ),
-            ],
+        react!(
+            <ListBoxElt>
+                <TextLabelElt text="Line 1" />
+                <TextLabelElt text="Line 2" />
+                <TextLabelElt text="Line 3" />
+                <ListBoxElt background_color={Color::from_rgb(32, 32, 96)}>
+                    <TextLabelElt text="the quick brown fox jumps over the lazy dog" />
+                </ListBoxElt>
+            </ListBoxElt>
         )
     }
 }
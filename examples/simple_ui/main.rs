@@ -27,17 +27,19 @@ impl MainState {
 
 impl event::EventHandler for MainState {
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        self.ui_tree.flush();
+        if let Err(err) = self.ui_tree.flush() {
+            eprintln!("ui update failed: {}", err);
+        }
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         graphics::clear(ctx, [0.1, 0.2, 0.3, 1.0].into());
 
-        let widget = self.ui_tree.render::<Widget>();
-
-        if let Some(mut widget) = widget {
-            widget.draw(ctx)?;
+        match self.ui_tree.render::<Widget>() {
+            Ok(Some(mut widget)) => widget.draw(ctx)?,
+            Ok(None) => (),
+            Err(err) => eprintln!("ui render failed: {}", err),
         }
 
         graphics::present(ctx)?;
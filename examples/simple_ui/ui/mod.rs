@@ -81,7 +81,7 @@ impl<'a> Widget<'a> {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, HostElement)]
 pub enum WidgetElement {
     TextLabel(TextLabelElt),
     ListBox(ListBoxElt),
@@ -91,8 +91,6 @@ pub type Element = react_rs::Element<WidgetElement>;
 pub use react_rs::Component;
 pub type RenderContext<'a, Class> = react_rs::RenderContext<'a, WidgetElement, Class>;
 
-impl HostElement for WidgetElement {}
-
 #[derive(PartialEq)]
 pub enum Font {
     Default,
@@ -117,12 +115,6 @@ impl Default for TextLabelElt {
     }
 }
 
-impl Into<WidgetElement> for TextLabelElt {
-    fn into(self) -> WidgetElement {
-        WidgetElement::TextLabel(self)
-    }
-}
-
 #[derive(PartialEq)]
 pub struct ListBoxElt {
     pub padding: f32,
@@ -137,9 +129,3 @@ impl Default for ListBoxElt {
         }
     }
 }
-
-impl Into<WidgetElement> for ListBoxElt {
-    fn into(self) -> WidgetElement {
-        WidgetElement::ListBox(self)
-    }
-}
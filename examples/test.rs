@@ -156,7 +156,7 @@ fn main() {
     let mut tree = react_rs::VirtualTree::<WidgetElement>::mount(element);
 
     {
-        let node = tree.render::<Widget>();
+        let node = tree.render::<Widget>().unwrap();
         println!("{:#?}", node);
         let poke: &RefCell<dyn FnMut()> = match node.as_ref() {
             Some(Widget { children, .. }) => match children[1].children[0].element {
@@ -173,18 +173,18 @@ fn main() {
     }
 
     //let element = Element::new_stateful::<App>("App".to_owned());
-    tree.flush();
+    tree.flush().unwrap();
 
     {
-        let node = tree.render::<Widget>();
+        let node = tree.render::<Widget>().unwrap();
         println!("{:#?}", node);
     }
 
     let element = Element::new_stateful::<App>("App 2.0".to_owned());
-    tree.update(element);
+    tree.update(element).unwrap();
 
     {
-        let node = tree.render::<Widget>();
+        let node = tree.render::<Widget>().unwrap();
         println!("{:#?}", node);
     }
 
@@ -0,0 +1,365 @@
+use proc_macro2::{Span, TokenStream};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, Ident, Type};
+
+/// Method names the builder defines itself, which fields can't use.
+const RESERVED: &[&str] = &["build", "child", "children", "into_element"];
+
+/// The contents of one `#[builder(...)]` attribute.
+#[derive(Default)]
+struct Options {
+    required: bool,
+    default: Option<Option<Expr>>,
+    component: Option<Type>,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Options> {
+        let content;
+        parenthesized!(content in input);
+
+        let mut options = Options::default();
+        while !content.is_empty() {
+            let name: Ident = content.parse()?;
+            if name == "required" {
+                options.required = true;
+            } else if name == "default" {
+                if content.peek(Token![=]) {
+                    content.parse::<Token![=]>()?;
+                    options.default = Some(Some(content.parse()?));
+                } else {
+                    options.default = Some(None);
+                }
+            } else if name == "component" {
+                content.parse::<Token![=]>()?;
+                options.component = Some(content.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "expected `required`, `default` or `component`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn options(attrs: &[Attribute]) -> syn::Result<Options> {
+    let mut merged = Options::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("builder")) {
+        let options: Options = syn::parse2(attr.tts.clone())?;
+        merged.required |= options.required;
+        merged.default = options.default.or(merged.default);
+        merged.component = options.component.or(merged.component);
+    }
+    Ok(merged)
+}
+
+fn is_string(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) => path.qself.is_none() && path.path.is_ident("String"),
+        _ => false,
+    }
+}
+
+pub fn expand(input: TokenStream) -> TokenStream {
+    syn::parse2(input)
+        .and_then(expand_struct)
+        .unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn expand_struct(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "Builder can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "Builder can only be derived for structs",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "Builder can't be derived for generic structs",
+        ));
+    }
+
+    let struct_options = options(&input.attrs)?;
+    if struct_options.required {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`required` applies to fields, not to the whole struct",
+        ));
+    }
+    if let Some(Some(ref expr)) = struct_options.default {
+        return Err(syn::Error::new(
+            expr.span(),
+            "use `#[builder(default)]` to take defaults from the struct's `Default` impl",
+        ));
+    }
+    let struct_default = struct_options.default.is_some();
+
+    let mut required_fields = vec![];
+    let mut required_params = vec![];
+    let mut required_names = vec![];
+    let mut optional_fields = vec![];
+    let mut optional_names = vec![];
+    let mut setters = vec![];
+    let mut finishers = vec![];
+    let mut moves = vec![];
+    for field in fields {
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        if RESERVED.iter().any(|reserved| name == reserved) {
+            return Err(syn::Error::new(
+                name.span(),
+                "this field name is used by one of the builder's own methods",
+            ));
+        }
+
+        let field_options = options(&field.attrs)?;
+        moves.push(quote!(#name: self.#name));
+        if let Some(ty) = field_options.component {
+            return Err(syn::Error::new(
+                ty.span(),
+                "`component` applies to the whole struct, not to a field",
+            ));
+        }
+
+        // Only `String` fields take `impl Into`, since it would stop
+        // numeric literals from being inferred as the field's type.
+        let param = if is_string(ty) {
+            quote!(impl ::std::convert::Into<#ty>)
+        } else {
+            quote!(#ty)
+        };
+
+        if field_options.required {
+            required_fields.push(quote!(#name: #ty));
+            required_params.push(quote!(#name: #param));
+            required_names.push(name);
+            finishers.push(quote!(#name: self.#name));
+            continue;
+        }
+
+        let default = match field_options.default {
+            Some(Some(expr)) => quote!(#expr),
+            _ if struct_default => quote!(defaults.#name),
+            _ => quote!(::std::default::Default::default()),
+        };
+        optional_fields.push(quote!(#name: ::std::option::Option<#ty>));
+        optional_names.push(name);
+        setters.push(quote! {
+            pub fn #name(mut self, value: #param) -> Self {
+                self.#name = ::std::option::Option::Some(::std::convert::Into::into(value));
+                self
+            }
+        });
+        // A `match` rather than `unwrap_or_else`, since a closure would
+        // move all of `defaults` in the 2015 edition.
+        finishers.push(quote! {
+            #name: match self.#name {
+                ::std::option::Option::Some(value) => value,
+                ::std::option::Option::None => #default,
+            }
+        });
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let builder = Ident::new(&format!("{}Builder", name), Span::call_site());
+    let defaults = if struct_default {
+        quote!(let defaults = <#name as ::std::default::Default>::default();)
+    } else {
+        quote!()
+    };
+    let required_fields = &required_fields;
+    let required_params = &required_params;
+    let required_names = &required_names;
+    let required_values = required_names;
+    let optional_fields = &optional_fields;
+    let optional_names = &optional_names;
+    let setters = &setters;
+    let finishers = &finishers;
+    let moves = &moves;
+    let build_doc = format!("Finishes the builder, producing a `{}`.", name);
+
+    let output = match struct_options.component {
+        Some(component) => {
+            let doc = format!("Builds the props of `{}`.", quote!(#component));
+            quote! {
+                #[doc = #doc]
+                #vis struct #builder {
+                    #(#required_fields,)*
+                    #(#optional_fields,)*
+                }
+
+                impl #name {
+                    #vis fn builder(#(#required_params),*) -> #builder {
+                        #builder {
+                            #(#required_names: ::std::convert::Into::into(#required_values),)*
+                            #(#optional_names: ::std::option::Option::None,)*
+                        }
+                    }
+                }
+
+                impl #builder {
+                    #(#setters)*
+
+                    #[doc = #build_doc]
+                    pub fn build(self) -> #name {
+                        #defaults
+                        #name {
+                            #(#finishers,)*
+                        }
+                    }
+
+                    pub fn into_element<H>(self) -> ::react_rs::Element<H>
+                    where
+                        H: ::react_rs::HostElement,
+                        #component: ::react_rs::Component<H, Props = #name> + 'static,
+                    {
+                        ::react_rs::Element::new_stateful::<#component>(self.build())
+                    }
+                }
+
+                impl<H> ::std::convert::From<#builder> for ::react_rs::Element<H>
+                where
+                    H: ::react_rs::HostElement,
+                    #component: ::react_rs::Component<H, Props = #name> + 'static,
+                {
+                    fn from(builder: #builder) -> ::react_rs::Element<H> {
+                        builder.into_element()
+                    }
+                }
+            }
+        }
+        None => {
+            // The builder only takes on a host type once it has children,
+            // so `build` and childless `into_element` calls infer it.
+            let doc = format!("Builds a `{}` host element and its children.", name);
+            quote! {
+                #[doc = #doc]
+                #vis struct #builder<C = ()> {
+                    #(#required_fields,)*
+                    #(#optional_fields,)*
+                    children: C,
+                }
+
+                impl #name {
+                    #vis fn builder(#(#required_params),*) -> #builder {
+                        #builder {
+                            #(#required_names: ::std::convert::Into::into(#required_values),)*
+                            #(#optional_names: ::std::option::Option::None,)*
+                            children: (),
+                        }
+                    }
+                }
+
+                impl<C> #builder<C> {
+                    #(#setters)*
+
+                    /// Finishes the element itself, dropping any children.
+                    pub fn build(self) -> #name {
+                        #defaults
+                        #name {
+                            #(#finishers,)*
+                        }
+                    }
+                }
+
+                impl #builder {
+                    pub fn child<H, E>(self, child: E) -> #builder<::std::vec::Vec<::react_rs::Element<H>>>
+                    where
+                        H: ::react_rs::HostElement,
+                        E: ::std::convert::Into<::react_rs::Element<H>>,
+                    {
+                        self.children(::std::iter::once(child))
+                    }
+
+                    pub fn children<H, I>(self, children: I) -> #builder<::std::vec::Vec<::react_rs::Element<H>>>
+                    where
+                        H: ::react_rs::HostElement,
+                        I: ::std::iter::IntoIterator,
+                        I::Item: ::std::convert::Into<::react_rs::Element<H>>,
+                    {
+                        #builder {
+                            #(#moves,)*
+                            children: children.into_iter().map(::std::convert::Into::into).collect(),
+                        }
+                    }
+
+                    pub fn into_element<H>(self) -> ::react_rs::Element<H>
+                    where
+                        H: ::react_rs::HostElement,
+                        #name: ::std::convert::Into<H>,
+                    {
+                        ::react_rs::Element::new_host(self.build(), ::std::vec::Vec::new())
+                    }
+                }
+
+                impl<H: ::react_rs::HostElement> #builder<::std::vec::Vec<::react_rs::Element<H>>> {
+                    pub fn child<E>(mut self, child: E) -> Self
+                    where
+                        E: ::std::convert::Into<::react_rs::Element<H>>,
+                    {
+                        self.children.push(child.into());
+                        self
+                    }
+
+                    pub fn children<I>(mut self, children: I) -> Self
+                    where
+                        I: ::std::iter::IntoIterator,
+                        I::Item: ::std::convert::Into<::react_rs::Element<H>>,
+                    {
+                        self.children.extend(children.into_iter().map(::std::convert::Into::into));
+                        self
+                    }
+
+                    pub fn into_element(mut self) -> ::react_rs::Element<H>
+                    where
+                        #name: ::std::convert::Into<H>,
+                    {
+                        let children = ::std::mem::replace(&mut self.children, ::std::vec::Vec::new());
+                        ::react_rs::Element::new_host(self.build(), children)
+                    }
+                }
+
+                impl<H> ::std::convert::From<#builder> for ::react_rs::Element<H>
+                where
+                    H: ::react_rs::HostElement,
+                    #name: ::std::convert::Into<H>,
+                {
+                    fn from(builder: #builder) -> ::react_rs::Element<H> {
+                        builder.into_element()
+                    }
+                }
+
+                impl<H> ::std::convert::From<#builder<::std::vec::Vec<::react_rs::Element<H>>>> for ::react_rs::Element<H>
+                where
+                    H: ::react_rs::HostElement,
+                    #name: ::std::convert::Into<H>,
+                {
+                    fn from(builder: #builder<::std::vec::Vec<::react_rs::Element<H>>>) -> ::react_rs::Element<H> {
+                        builder.into_element()
+                    }
+                }
+            }
+        }
+    };
+    Ok(output)
+}
@@ -0,0 +1,351 @@
+use proc_macro2::{Span, TokenStream};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Block, FnArg, Ident, ImplItem, Item, ItemFn, ItemImpl, Stmt, Type};
+
+const LIFECYCLE_METHODS: &[&str] = &["did_mount", "did_update", "will_unmount"];
+
+/// `#[component(Host, props = Type, state = Type)]`
+struct Args {
+    host: Type,
+    props: Option<Type>,
+    state: Option<Type>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Args> {
+        let mut args = Args {
+            host: input.parse()?,
+            props: None,
+            state: None,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let name: Ident = input.parse()?;
+            if name != "props" && name != "state" {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "expected `props = Type` or `state = Type`",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            let ty = input.parse()?;
+            if name == "props" {
+                args.props = Some(ty);
+            } else {
+                args.state = Some(ty);
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+pub fn expand(args: TokenStream, item: TokenStream) -> TokenStream {
+    let result = syn::parse2::<Args>(args).and_then(|args| match syn::parse2(item)? {
+        Item::Fn(item) => expand_fn(args, item),
+        Item::Impl(item) => expand_impl(args, item),
+        item => Err(syn::Error::new(
+            item.span(),
+            "#[component] can only be used on a function or on a struct's impl block",
+        )),
+    });
+    result.unwrap_or_else(|err| err.to_compile_error())
+}
+
+/// The referenced type of a `&Type` parameter.
+fn referent(ty: &Type, what: &str) -> syn::Result<Type> {
+    match *ty {
+        Type::Reference(ref reference) => Ok((*reference.elem).clone()),
+        _ => Err(syn::Error::new(
+            ty.span(),
+            format!("expected the {} to be taken by reference", what),
+        )),
+    }
+}
+
+/// Splits nested `fn did_mount() { ... }` items out of a function
+/// component's body.
+fn take_lifecycle_methods(block: &mut Block) -> syn::Result<Vec<ItemFn>> {
+    let mut methods = vec![];
+    let mut stmts = vec![];
+    for stmt in block.stmts.drain(..) {
+        match stmt {
+            Stmt::Item(Item::Fn(ref method))
+                if LIFECYCLE_METHODS.iter().any(|name| method.ident == name) =>
+            {
+                if !method.decl.inputs.is_empty() {
+                    return Err(syn::Error::new(
+                        method.decl.inputs.span(),
+                        "lifecycle methods of a function component take no parameters",
+                    ));
+                }
+                methods.push(method.clone());
+            }
+            stmt => stmts.push(stmt),
+        }
+    }
+    block.stmts = stmts;
+    Ok(methods)
+}
+
+fn expand_fn(args: Args, mut item: ItemFn) -> syn::Result<TokenStream> {
+    if let Some(ty) = args.props.as_ref().or(args.state.as_ref()) {
+        return Err(syn::Error::new(
+            ty.span(),
+            "the props and state of a function component come from its parameters",
+        ));
+    }
+    if !item.decl.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.decl.generics.span(),
+            "function components can't be generic",
+        ));
+    }
+    if item.decl.inputs.len() > 3 {
+        return Err(syn::Error::new(
+            item.decl.inputs.span(),
+            "expected at most `props`, `state` and `updater` parameters",
+        ));
+    }
+
+    let mut patterns = vec![];
+    let mut types = vec![];
+    for input in &item.decl.inputs {
+        match *input {
+            FnArg::Captured(ref arg) => {
+                patterns.push(arg.pat.clone());
+                types.push(arg.ty.clone());
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "expected a parameter of the form `name: Type`",
+                ))
+            }
+        }
+    }
+    let sources = [quote!(ctx.props), quote!(ctx.state), quote!(ctx.updater)];
+    let sources = &sources[..types.len()];
+
+    let unit = quote!(());
+    let props = match types.first() {
+        Some(ty) => referent(ty, "props")?,
+        None => syn::parse2(unit.clone())?,
+    };
+    let state = match types.get(1) {
+        Some(ty) => referent(ty, "state")?,
+        None => syn::parse2(unit)?,
+    };
+
+    let lifecycle = take_lifecycle_methods(&mut item.block)?
+        .into_iter()
+        .map(|method| {
+            let name = method.ident;
+            let body = method.block;
+            quote!(fn #name(&mut self) #body)
+        });
+
+    let attrs = &item.attrs;
+    let vis = &item.vis;
+    let name = &item.ident;
+    let host = &args.host;
+    let stmts = &item.block.stmts;
+    let ctx = if types.is_empty() {
+        Ident::new("_ctx", Span::call_site())
+    } else {
+        Ident::new("ctx", Span::call_site())
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis struct #name;
+
+        impl ::react_rs::Component<#host> for #name {
+            type Props = #props;
+            type State = #state;
+
+            fn create(_initial_props: &Self::Props) -> (Self, Self::State) {
+                (#name, ::std::default::Default::default())
+            }
+
+            fn render(
+                &self,
+                #ctx: ::react_rs::RenderContext<#host, Self>,
+            ) -> ::react_rs::Element<#host> {
+                let (#(#patterns,)*): (#(#types,)*) = (#(#sources,)*);
+                #(#stmts)*
+            }
+
+            #(#lifecycle)*
+        }
+    })
+}
+
+fn expand_impl(args: Args, item: ItemImpl) -> syn::Result<TokenStream> {
+    if let Some((_, ref path, _)) = item.trait_ {
+        return Err(syn::Error::new(
+            path.span(),
+            "#[component] goes on an inherent impl block, not a trait impl",
+        ));
+    }
+    let methods = item
+        .items
+        .iter()
+        .filter_map(|item| match *item {
+            ImplItem::Method(ref method) => Some(method.sig.ident.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !methods.iter().any(|method| method == "render") {
+        return Err(syn::Error::new(
+            item.self_ty.span(),
+            "missing a `render` method, which `Component::render` calls",
+        ));
+    }
+
+    let unit = quote!(());
+    let props = match args.props {
+        Some(props) => props,
+        None => syn::parse2(unit.clone())?,
+    };
+    let state = match args.state {
+        Some(state) => state,
+        None => syn::parse2(unit)?,
+    };
+
+    let host = &args.host;
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let lifecycle = methods
+        .iter()
+        .filter(|method| LIFECYCLE_METHODS.iter().any(|name| *method == name))
+        .map(|method| {
+            quote! {
+                fn #method(&mut self) {
+                    <#self_ty>::#method(self)
+                }
+            }
+        });
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::react_rs::Component<#host> for #self_ty #where_clause {
+            type Props = #props;
+            type State = #state;
+
+            fn create(_initial_props: &Self::Props) -> (Self, Self::State) {
+                (
+                    ::std::default::Default::default(),
+                    ::std::default::Default::default(),
+                )
+            }
+
+            fn render(
+                &self,
+                ctx: ::react_rs::RenderContext<#host, Self>,
+            ) -> ::react_rs::Element<#host> {
+                <#self_ty>::render(self, ctx)
+            }
+
+            #(#lifecycle)*
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use proc_macro2::TokenStream;
+
+    fn assert_fails(args: TokenStream, item: TokenStream, message: &str) {
+        let output = expand(args, item).to_string();
+        assert!(output.starts_with("compile_error"), "{}", output);
+        assert!(output.contains(message), "{}", output);
+    }
+
+    #[test]
+    fn forwards_to_the_impl_blocks_methods() {
+        let output = expand(
+            quote!(Widget, props = u32),
+            quote!(impl Toggle {
+                fn render(&self, ctx: RenderContext<Widget, Toggle>) -> Element<Widget> {
+                    ctx.props.into()
+                }
+                fn did_update(&mut self) {}
+                fn helper(&self) {}
+            }),
+        )
+        .to_string();
+        let forwarded = |method: &str| {
+            let call = quote!(<Toggle>::method(self)).to_string();
+            output.contains(&call.replace("method", method))
+        };
+        assert!(forwarded("did_update"), "{}", output);
+        assert!(!forwarded("did_mount"), "{}", output);
+        assert!(!forwarded("helper"), "{}", output);
+        assert!(
+            output.contains(
+                &quote!(
+                    type Props = u32;
+                )
+                .to_string()
+            ),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains(
+                &quote!(
+                    type State = ();
+                )
+                .to_string()
+            ),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn rejects_misplaced_components() {
+        assert_fails(
+            quote!(Widget),
+            quote!(impl Toggle {
+                fn did_mount(&mut self) {}
+            }),
+            "missing a `render` method",
+        );
+        assert_fails(
+            quote!(Widget),
+            quote!(impl Clone for Toggle {
+                fn render(&self) {}
+            }),
+            "not a trait impl",
+        );
+        assert_fails(
+            quote!(Widget),
+            quote!(
+                struct Toggle;
+            ),
+            "on a function or on a struct's impl block",
+        );
+        assert_fails(
+            quote!(Widget, did_mount),
+            quote!(impl Toggle {}),
+            "expected `props = Type` or `state = Type`",
+        );
+        assert_fails(
+            quote!(Widget, props = u32),
+            quote!(
+                fn Counter(step: &u32) -> Element {}
+            ),
+            "come from its parameters",
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use syn::parse::{ParseStream, Parser};
+use syn::Ident;
+
+/// One node of `react!` markup.
+enum Item {
+    Tag {
+        name: Ident,
+        attributes: Vec<(Ident, TokenTree)>,
+        children: Vec<Item>,
+    },
+    Fragment(Vec<Item>),
+    Content(TokenTree),
+}
+
+pub fn expand(input: TokenStream) -> TokenStream {
+    parse_markup
+        .parse2(input)
+        .and_then(expand_item)
+        .unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn parse_markup(input: ParseStream) -> syn::Result<Item> {
+    if input.is_empty() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "expected an element, like `<Tag />`",
+        ));
+    }
+    let item = parse_item(input)?;
+    if !input.is_empty() {
+        return Err(input.error("react! takes a single element; wrap several in `<>...</>`"));
+    }
+    Ok(item)
+}
+
+fn parse_item(input: ParseStream) -> syn::Result<Item> {
+    if !input.peek(Token![<]) {
+        return Ok(Item::Content(input.parse()?));
+    }
+    input.parse::<Token![<]>()?;
+
+    if input.peek(Token![>]) {
+        input.parse::<Token![>]>()?;
+        let children = parse_children(input, None)?;
+        return Ok(Item::Fragment(children));
+    }
+
+    let name: Ident = input.parse()?;
+    let mut attributes = vec![];
+    while !input.peek(Token![/]) && !input.peek(Token![>]) {
+        if input.is_empty() {
+            return Err(syn::Error::new(name.span(), "unclosed tag"));
+        }
+        let attribute: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        attributes.push((attribute, parse_value(input)?));
+    }
+
+    let children = if input.peek(Token![/]) {
+        input.parse::<Token![/]>()?;
+        input.parse::<Token![>]>()?;
+        vec![]
+    } else {
+        input.parse::<Token![>]>()?;
+        parse_children(input, Some(&name))?
+    };
+    Ok(Item::Tag {
+        name,
+        attributes,
+        children,
+    })
+}
+
+/// Parses an attribute value. Values are a single token, so that where
+/// one ends and the next attribute starts is never ambiguous.
+fn parse_value(input: ParseStream) -> syn::Result<TokenTree> {
+    let value: TokenTree = input.parse()?;
+    let single_token = match value {
+        TokenTree::Literal(_) | TokenTree::Ident(_) => true,
+        TokenTree::Group(ref group) => group.delimiter() == Delimiter::Brace,
+        TokenTree::Punct(_) => false,
+    };
+    let ends = input.is_empty()
+        || input.peek(Token![/])
+        || input.peek(Token![>])
+        || input.cursor().ident().is_some();
+    if !single_token || !ends {
+        return Err(syn::Error::new(
+            value.span(),
+            "attribute values must be a literal, identifier or `{ expr }`",
+        ));
+    }
+    Ok(value)
+}
+
+/// Parses children up to and including the closing tag for `name`, or
+/// for a fragment when `name` is `None`.
+fn parse_children(input: ParseStream, name: Option<&Ident>) -> syn::Result<Vec<Item>> {
+    let mut children = vec![];
+    while !(input.peek(Token![<]) && input.peek2(Token![/])) {
+        if input.is_empty() {
+            return Err(match name {
+                Some(name) => syn::Error::new(name.span(), format!("missing `</{}>`", name)),
+                None => syn::Error::new(Span::call_site(), "missing `</>`"),
+            });
+        }
+        children.push(parse_item(input)?);
+    }
+
+    input.parse::<Token![<]>()?;
+    input.parse::<Token![/]>()?;
+    match name {
+        Some(name) => {
+            let closing: Ident = input.parse()?;
+            if closing != *name {
+                return Err(syn::Error::new(
+                    closing.span(),
+                    format!("expected `</{}>`", name),
+                ));
+            }
+        }
+        None => {
+            if !input.peek(Token![>]) {
+                return Err(input.error("expected `</>`"));
+            }
+        }
+    }
+    input.parse::<Token![>]>()?;
+    Ok(children)
+}
+
+fn expand_item(item: Item) -> syn::Result<TokenStream> {
+    match item {
+        Item::Tag {
+            name,
+            attributes,
+            children,
+        } => expand_tag(name, attributes, children),
+        Item::Fragment(children) => {
+            let children = expand_children(children)?;
+            Ok(quote!(::react_rs::Element::new_fragment(
+                vec![#(#children),*]
+            )))
+        }
+        Item::Content(token) => expand_content(token),
+    }
+}
+
+fn expand_children(children: Vec<Item>) -> syn::Result<Vec<TokenStream>> {
+    children.into_iter().map(expand_item).collect()
+}
+
+fn expand_content(token: TokenTree) -> syn::Result<TokenStream> {
+    match token {
+        TokenTree::Group(ref group) if group.delimiter() == Delimiter::Brace => {
+            let expr = group.stream();
+            Ok(quote_spanned!(group.span()=> ::react_rs::Element::from(#expr)))
+        }
+        _ => Err(syn::Error::new(
+            token.span(),
+            "expected a tag or a `{ ... }` block; text has to be passed as an attribute",
+        )),
+    }
+}
+
+/// Strips the braces from a `{ expr }` attribute value that's passed as
+/// a function argument, which would otherwise trip `unused_braces` in
+/// the caller's crate.
+fn unbrace(value: TokenTree) -> TokenStream {
+    match value {
+        TokenTree::Group(ref group) if group.delimiter() == Delimiter::Brace => group.stream(),
+        value => quote!(#value),
+    }
+}
+
+fn is_string_literal(value: &TokenTree) -> bool {
+    match *value {
+        TokenTree::Literal(ref literal) => {
+            let literal = literal.to_string();
+            literal.starts_with('"') || literal.starts_with("r\"") || literal.starts_with("r#")
+        }
+        _ => false,
+    }
+}
+
+fn expand_tag(
+    name: Ident,
+    attributes: Vec<(Ident, TokenTree)>,
+    children: Vec<Item>,
+) -> syn::Result<TokenStream> {
+    let mut props = None;
+    let mut fields = vec![];
+
+    for (attribute, value) in attributes {
+        if attribute == "props" {
+            props = Some(value);
+        } else if attribute == "key" {
+            return Err(syn::Error::new(
+                attribute.span(),
+                "element keys aren't supported by the reconciler yet",
+            ));
+        } else if is_string_literal(&value) {
+            fields.push(quote!(#attribute: ::std::convert::Into::into(#value)));
+        } else {
+            fields.push(quote!(#attribute: #value));
+        }
+    }
+
+    match props {
+        Some(props) => {
+            if !fields.is_empty() {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "a component with `props` can't have other attributes",
+                ));
+            }
+            if !children.is_empty() {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "components can't have children; pass them through `props`",
+                ));
+            }
+            let props = unbrace(props);
+            Ok(quote_spanned!(name.span()=> ::react_rs::Element::new_stateful::<#name>(#props)))
+        }
+        None => {
+            let children = expand_children(children)?;
+            Ok(quote!(::react_rs::Element::new_host(
+                #name {
+                    #(#fields,)*
+                    ..::std::default::Default::default()
+                },
+                vec![#(#children),*],
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use proc_macro2::TokenStream;
+
+    fn assert_expands(input: TokenStream, expected: TokenStream) {
+        assert_eq!(expand(input).to_string(), expected.to_string());
+    }
+
+    fn assert_fails(input: TokenStream, message: &str) {
+        let output = expand(input).to_string();
+        assert!(output.starts_with("compile_error"), "{}", output);
+        assert!(output.contains(message), "{}", output);
+    }
+
+    #[test]
+    fn expands_host_elements() {
+        assert_expands(
+            quote!(<ListBoxElt padding=4.0>
+                <TextLabelElt text="Line 1" />
+                { children }
+            </ListBoxElt>),
+            quote!(::react_rs::Element::new_host(
+                ListBoxElt {
+                    padding: 4.0,
+                    ..::std::default::Default::default()
+                },
+                vec![
+                    ::react_rs::Element::new_host(
+                        TextLabelElt {
+                            text: ::std::convert::Into::into("Line 1"),
+                            ..::std::default::Default::default()
+                        },
+                        vec![],
+                    ),
+                    ::react_rs::Element::from(children)
+                ],
+            )),
+        );
+    }
+
+    #[test]
+    fn expands_components_and_fragments() {
+        assert_expands(
+            quote!(<>
+                <Counter props={ 5 } />
+                <Counter props=step />
+            </>),
+            quote!(::react_rs::Element::new_fragment(vec![
+                ::react_rs::Element::new_stateful::<Counter>(5),
+                ::react_rs::Element::new_stateful::<Counter>(step)
+            ])),
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_markup() {
+        assert_fails(quote!(), "expected an element");
+        assert_fails(quote!(<A></B>), "expected `</A>`");
+        assert_fails(quote!(<A><B /></A> <C />), "single element");
+        assert_fails(quote!(<A>), "missing `</A>`");
+        assert_fails(quote!(<><A /></A>), "expected `</>`");
+        assert_fails(
+            quote!(<A>"text"</A>),
+            "text has to be passed as an attribute",
+        );
+        for markup in &[
+            quote!(<A x=-1 />),
+            quote!(<A x=a.b />),
+            quote!(<A x=(1) y=2 />),
+        ] {
+            assert_fails(
+                markup.clone(),
+                "must be a literal, identifier or `{ expr }`",
+            );
+        }
+        assert_fails(quote!(<A key=1 />), "keys aren't supported");
+        assert_fails(quote!(<A props={()} x=1 />), "can't have other attributes");
+        assert_fails(quote!(<A props={()}><B /></A>), "can't have children");
+    }
+}
@@ -0,0 +1,108 @@
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields};
+
+pub fn expand(input: TokenStream) -> TokenStream {
+    syn::parse2(input)
+        .and_then(expand_enum)
+        .unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn expand_enum(input: DeriveInput) -> syn::Result<TokenStream> {
+    let data = match input.data {
+        Data::Enum(ref data) => data,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "HostElement can only be derived for an enum of element structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut seen = vec![];
+    let mut conversions = vec![];
+    for variant in &data.variants {
+        let field = match variant.fields {
+            Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+            _ => return Err(syn::Error::new(
+                variant.span(),
+                "each variant has to wrap exactly one element struct, as in `Button(ButtonElt)`",
+            )),
+        };
+
+        let ty = &field.ty;
+        let key = quote!(#ty).to_string();
+        if seen.contains(&key) {
+            return Err(syn::Error::new(
+                ty.span(),
+                "this element struct is already wrapped by another variant",
+            ));
+        }
+        seen.push(key);
+
+        let variant = &variant.ident;
+        conversions.push(quote! {
+            impl #impl_generics ::std::convert::From<#ty> for #name #ty_generics #where_clause {
+                fn from(element: #ty) -> #name #ty_generics {
+                    #name::#variant(element)
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::react_rs::HostElement for #name #ty_generics #where_clause {}
+
+        #(#conversions)*
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use proc_macro2::TokenStream;
+
+    fn assert_fails(input: TokenStream, message: &str) {
+        let output = expand(input).to_string();
+        assert!(output.starts_with("compile_error"), "{}", output);
+        assert!(output.contains(message), "{}", output);
+    }
+
+    #[test]
+    fn rejects_anything_but_an_enum_of_element_structs() {
+        assert_fails(
+            quote!(
+                struct Widget(TextLabelElt);
+            ),
+            "can only be derived for an enum",
+        );
+        assert_fails(
+            quote!(
+                enum Widget {
+                    Empty,
+                }
+            ),
+            "has to wrap exactly one element struct",
+        );
+        assert_fails(
+            quote!(
+                enum Widget {
+                    Pair(TextLabelElt, ListBoxElt),
+                }
+            ),
+            "has to wrap exactly one element struct",
+        );
+        assert_fails(
+            quote!(
+                enum Widget {
+                    First(TextLabelElt),
+                    Second(TextLabelElt),
+                }
+            ),
+            "already wrapped by another variant",
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Procedural macros for react-rs. Use them through the re-exports in
+//! the `react_rs` crate.
+
+#![recursion_limit = "256"]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+mod builder;
+mod component;
+mod element;
+mod host_element;
+
+/// Builds an `Element` from JSX-like markup.
+///
+/// ```ignore
+/// react!(
+///     <ListBoxElt padding=4.0>
+///         <TextLabelElt text="Line 1" />
+///         <Counter props={()} />
+///         <>
+///             { children }
+///         </>
+///     </ListBoxElt>
+/// )
+/// ```
+///
+/// - A tag is a host element. Its attributes become fields of a struct
+///   literal, the remaining fields come from `Default`, and the result
+///   goes through `Element::new_host`. String literals are passed
+///   through `Into::into`, so they can initialize `String` fields.
+/// - A tag with a `props` attribute is a stateful component and
+///   becomes `Element::new_stateful::<Tag>(props)`. It can't have other
+///   attributes or children.
+/// - `<>...</>` becomes `Element::new_fragment`.
+/// - `{ expr }` inserts anything that converts into an `Element`,
+///   including a `Vec` of elements, which becomes a fragment.
+///
+/// An attribute value is a single token: a literal, an identifier or a
+/// `{ expr }` block. The reconciler doesn't support element keys yet, so
+/// a `key` attribute is an error.
+#[proc_macro]
+pub fn react(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    element::expand(input.into()).into()
+}
+
+/// Generates a `Component` impl from a function or a struct.
+///
+/// On a function, the function becomes `render` and its name becomes a
+/// unit struct. Its parameters are, in order and all optional, the props
+/// and state by reference and the `StateUpdater`, so `Props` and `State`
+/// are inferred from their types and default to `()`. The state starts
+/// out as `Default::default()`. Lifecycle methods are nested functions
+/// without parameters:
+///
+/// ```ignore
+/// #[component(WidgetElement)]
+/// fn Counter(step: &u32, count: &u32, updater: StateUpdater<WidgetElement, Counter>) -> Element {
+///     fn did_mount() {
+///         println!("counter mounted");
+///     }
+///
+///     react!(<TextLabelElt text={format!("{}", count)} />)
+/// }
+/// ```
+///
+/// On the inherent impl block of a struct, `Props` and `State` are
+/// given as arguments and default to `()`. The component and its state
+/// are both created with `Default::default()`, `render` calls the
+/// block's `render` method, which has the same signature, and the
+/// lifecycle methods call the block's methods of the same name, if it
+/// has them. A block without a `render` method is an error:
+///
+/// ```ignore
+/// #[derive(Default)]
+/// struct Toggle {
+///     mounted_at: Option<Instant>,
+/// }
+///
+/// #[component(WidgetElement, props = String, state = bool)]
+/// impl Toggle {
+///     fn render(&self, ctx: RenderContext<WidgetElement, Toggle>) -> Element {
+///         react!(<TextLabelElt text={ctx.props.clone()} />)
+///     }
+///
+///     fn did_mount(&mut self) {
+///         self.mounted_at = Some(Instant::now());
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn component(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    component::expand(args.into(), item.into()).into()
+}
+
+/// Implements `HostElement` for an enum whose variants each wrap one
+/// element struct, along with a `From` conversion from every element
+/// struct into the enum, so they can be passed to `Element::new_host`
+/// directly.
+///
+/// ```ignore
+/// #[derive(PartialEq, HostElement)]
+/// pub enum WidgetElement {
+///     TextLabel(TextLabelElt),
+///     ListBox(ListBoxElt),
+/// }
+/// ```
+#[proc_macro_derive(HostElement)]
+pub fn derive_host_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    host_element::expand(input.into()).into()
+}
+
+/// Generates a builder for a host element struct or a props struct, so
+/// elements can be built without `..Default::default()` and `vec![]`.
+///
+/// `Struct::builder(...)` takes the fields marked
+/// `#[builder(required)]` as parameters. Every other field gets a
+/// setter, and `String` fields accept anything that converts into a
+/// `String`. An unset field falls back
+/// to its `#[builder(default = expr)]`, or to `Default::default()` for
+/// its type, or to its value in the struct's own `Default` impl when the
+/// struct is marked `#[builder(default)]`.
+///
+/// A host element builder also collects children with `child` and
+/// `children`, and `into_element` finishes it with `Element::new_host`.
+/// The host type is inferred from the children or the finished element:
+///
+/// ```ignore
+/// #[derive(PartialEq, Builder)]
+/// #[builder(default)]
+/// pub struct ListBoxElt {
+///     pub padding: f32,
+///     pub background_color: Color,
+/// }
+///
+/// ListBoxElt::builder()
+///     .padding(8.0)
+///     .child(TextLabelElt::builder("Line 1"))
+///     .into_element()
+/// ```
+///
+/// With `#[builder(component = Class)]` the struct is taken to be the
+/// props of `Class`, and `into_element` finishes the builder with
+/// `Element::new_stateful::<Class>`.
+///
+/// Builders convert into `Element` as well, so they can be passed as
+/// children directly. Element keys aren't supported by the reconciler
+/// yet, so builders don't offer them.
+#[proc_macro_derive(Builder, attributes(builder))]
+pub fn derive_builder(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    builder::expand(input.into()).into()
+}
@@ -16,6 +16,7 @@ pub trait Component<H: HostElement>: Sized {
     fn create(initial_props: &Self::Props) -> (Self, Self::State);
 
     fn did_mount(&mut self) {}
+    fn did_update(&mut self) {}
     fn will_unmount(&mut self) {}
 
     fn get_derived_state_from_props(
@@ -24,4 +25,15 @@ pub trait Component<H: HostElement>: Sized {
     ) -> Self::State {
         last_state
     }
+
+    /// Text shown for this component's props by `VirtualTree::inspect`.
+    /// Usually `Some(format!("{:?}", props))` when the props are `Debug`.
+    fn debug_props(_props: &Self::Props) -> Option<String> {
+        None
+    }
+
+    /// Text shown for this component's state by `VirtualTree::inspect`.
+    fn debug_state(_state: &Self::State) -> Option<String> {
+        None
+    }
 }
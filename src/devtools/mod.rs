@@ -0,0 +1,400 @@
+//! Serves a live `VirtualTree` to an external inspector.
+//!
+//! Enabled with the `devtools` cargo feature. The host binds a
+//! `DevtoolsServer` once and calls `poll` every frame, after its usual
+//! `flush`. The server never blocks and never touches the tree outside
+//! of `poll`.
+//!
+//! # Protocol
+//!
+//! Clients connect over TCP or a Unix socket and exchange JSON objects,
+//! one per line. Every message has a `"type"` field. Nodes are referred
+//! to by ids of the form `"<index>v<generation>"`, which stay valid
+//! until the node is unmounted.
+//!
+//! Server to client:
+//!
+//! - `{"type": "hello", "protocol": 2, "revision": 4}` is sent once
+//!   when the client connects.
+//! - `{"type": "changed", "revision": 5}` is sent whenever the tree has
+//!   been updated or flushed since the last poll.
+//! - `{"type": "snapshot", "revision": 5, "root": "0v0", "nodes": [...]}`
+//!   answers a snapshot request. `nodes` is in pre-order.
+//! - `{"type": "selected", "node": {...}}` answers a select request.
+//! - `{"type": "error", "message": "..."}` reports a bad request.
+//!
+//! Each node is an object with the fields `key`, `parent`, `depth`,
+//! `kind` (`"host"`, `"stateful"`, `"fragment"` or `"layer"`),
+//! `type_name`, `children`, `element`, `layer` (`{"name": "popups",
+//! "z_index": 10}`), `props`, `state`, `render_reason` (`"mount"`,
+//! `"state_update"`, `"props_changed"` or `"unstable_props"`, see
+//! `RenderReason`) and `render_count`. Fields that don't apply to a node
+//! are `null`.
+//!
+//! Client to server:
+//!
+//! - `{"type": "snapshot"}` asks for the whole tree.
+//! - `{"type": "select", "node": "3v0"}` marks a node as selected.
+//! - `{"type": "highlight", "node": "3v0"}` asks the host to highlight
+//!   a node on screen. `"node": null` clears the highlight.
+//!
+//! Selection and highlight requests are also handed to the host as
+//! `DevtoolsRequest`s.
+
+use element::HostElement;
+use flat_tree::NodeKey;
+use reconciler::{VirtualNode, VirtualTree};
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::Path;
+
+mod protocol;
+
+use self::protocol::ClientMessage;
+pub use self::protocol::PROTOCOL_VERSION;
+
+/// Longest request line a client may send, in bytes. Clients that send
+/// more without a newline are dropped.
+const MAX_LINE: usize = 64 * 1024;
+
+/// Most output that may be queued for a client that isn't reading, in
+/// bytes. Clients that fall further behind are dropped.
+const MAX_BACKLOG: usize = 16 * 1024 * 1024;
+
+/// A request from an inspector that the host has to act on.
+#[derive(Debug, PartialEq)]
+pub enum DevtoolsRequest<H: HostElement> {
+    Select(NodeKey<VirtualNode<H>>),
+    Highlight(Option<NodeKey<VirtualNode<H>>>),
+}
+
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        match *self {
+            Listener::Tcp(ref listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(ref listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+struct Connection {
+    stream: Box<dyn Stream>,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn send(&mut self, message: &serde_json::Value) {
+        self.outgoing
+            .extend_from_slice(message.to_string().as_bytes());
+        self.outgoing.push(b'\n');
+    }
+
+    /// Reads whatever has arrived and returns the complete lines.
+    /// Returns an error once the peer has gone away, or if it sends a
+    /// line longer than `MAX_LINE`.
+    fn receive(&mut self) -> io::Result<Vec<String>> {
+        let mut buffer = [0; 4096];
+        let mut lines = vec![];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.incoming.extend_from_slice(&buffer[..len]),
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+
+            while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+                let line = self.incoming.drain(..=end).collect::<Vec<u8>>();
+                let line = String::from_utf8_lossy(&line).trim().to_owned();
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+            }
+            if self.incoming.len() > MAX_LINE {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "request line too long",
+                ));
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Writes as much of the queued output as the socket will take.
+    /// Fails if more than `MAX_BACKLOG` is left over.
+    fn write_pending(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.outgoing.drain(..len);
+                }
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        if self.outgoing.len() > MAX_BACKLOG {
+            return Err(io::Error::other("client isn't reading"));
+        }
+        Ok(())
+    }
+}
+
+pub struct DevtoolsServer<H: HostElement> {
+    listener: Listener,
+    connections: Vec<Connection>,
+    revision: Option<u64>,
+    selected: Option<NodeKey<VirtualNode<H>>>,
+}
+
+impl<H> DevtoolsServer<H>
+where
+    H: HostElement + fmt::Debug,
+{
+    fn new(listener: Listener) -> DevtoolsServer<H> {
+        DevtoolsServer {
+            listener,
+            connections: vec![],
+            revision: None,
+            selected: None,
+        }
+    }
+
+    /// Listens on a TCP address. This should normally be a loopback
+    /// address such as `127.0.0.1:8097`, since the protocol has no
+    /// authentication.
+    pub fn bind_tcp<A: ToSocketAddrs>(addr: A) -> io::Result<DevtoolsServer<H>> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(DevtoolsServer::new(Listener::Tcp(listener)))
+    }
+
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P) -> io::Result<DevtoolsServer<H>> {
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(DevtoolsServer::new(Listener::Unix(listener)))
+    }
+
+    /// The node most recently selected by an inspector, if it is still
+    /// mounted.
+    pub fn selected(&self, tree: &VirtualTree<H>) -> Option<NodeKey<VirtualNode<H>>> {
+        self.selected.filter(|&key| tree.contains(key))
+    }
+
+    /// Answers requests from connected inspectors, tells them about
+    /// changes to `tree` and accepts new ones. Connections that fail
+    /// are dropped.
+    pub fn poll(&mut self, tree: &VirtualTree<H>) -> Vec<DevtoolsRequest<H>> {
+        let changed = self.revision != Some(tree.revision());
+        self.revision = Some(tree.revision());
+
+        let mut requests = vec![];
+        let mut selected = self.selected;
+        self.connections.retain_mut(|connection| {
+            if changed {
+                connection.send(&protocol::changed(tree));
+            }
+
+            let lines = match connection.receive() {
+                Ok(lines) => lines,
+                Err(_) => return false,
+            };
+            for line in lines {
+                match protocol::decode(&line) {
+                    Ok(ClientMessage::Snapshot) => connection.send(&protocol::snapshot(tree)),
+                    Ok(ClientMessage::Select(key)) => match tree.inspect_node(key) {
+                        Ok(node) => {
+                            selected = Some(key);
+                            connection.send(&protocol::selected(&node));
+                            requests.push(DevtoolsRequest::Select(key));
+                        }
+                        Err(_) => connection.send(&protocol::not_mounted(key)),
+                    },
+                    Ok(ClientMessage::Highlight(key)) => match key {
+                        Some(key) if !tree.contains(key) => {
+                            connection.send(&protocol::not_mounted(key))
+                        }
+                        _ => requests.push(DevtoolsRequest::Highlight(key)),
+                    },
+                    Err(message) => connection.send(&protocol::error(&message)),
+                }
+            }
+
+            connection.write_pending().is_ok()
+        });
+        self.selected = selected;
+
+        self.accept(tree);
+
+        requests
+    }
+
+    fn accept(&mut self, tree: &VirtualTree<H>) {
+        loop {
+            match self.listener.accept() {
+                Ok(stream) => {
+                    let mut connection = Connection {
+                        stream,
+                        incoming: vec![],
+                        outgoing: vec![],
+                    };
+                    connection.send(&protocol::hello(tree));
+                    if connection.write_pending().is_ok() {
+                        self.connections.push(connection);
+                    }
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connection, DevtoolsServer, Listener, MAX_BACKLOG, MAX_LINE};
+    use reconciler::tests::host;
+    use reconciler::VirtualTree;
+    use serde_json::Value;
+    use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// A peer that sent `input` and never reads.
+    struct Stalled {
+        input: Vec<u8>,
+    }
+
+    impl Read for Stalled {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            if self.input.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let len = buffer.len().min(self.input.len());
+            buffer[..len].copy_from_slice(&self.input[..len]);
+            self.input.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for Stalled {
+        fn write(&mut self, _buffer: &[u8]) -> io::Result<usize> {
+            Err(ErrorKind::WouldBlock.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stalled(input: Vec<u8>) -> Connection {
+        Connection {
+            stream: Box::new(Stalled { input }),
+            incoming: vec![],
+            outgoing: vec![],
+        }
+    }
+
+    #[test]
+    fn splits_requests_into_lines() {
+        let mut connection = stalled(b"{\"type\": \"snapshot\"}\n\n{\"ty".to_vec());
+        assert_eq!(
+            connection.receive().unwrap(),
+            vec!["{\"type\": \"snapshot\"}"]
+        );
+        assert_eq!(connection.incoming, b"{\"ty");
+    }
+
+    #[test]
+    fn drops_clients_that_send_overlong_lines() {
+        let mut connection = stalled(vec![b' '; MAX_LINE]);
+        assert!(connection.receive().unwrap().is_empty());
+        let mut connection = stalled(vec![b' '; MAX_LINE + 1]);
+        assert!(connection.receive().is_err());
+    }
+
+    #[test]
+    fn drops_clients_that_stop_reading() {
+        let mut connection = stalled(vec![]);
+        connection.send(&Value::String("x".repeat(MAX_BACKLOG / 2)));
+        assert!(connection.write_pending().is_ok());
+        connection.send(&Value::String("x".repeat(MAX_BACKLOG / 2)));
+        assert!(connection.write_pending().is_err());
+    }
+
+    #[test]
+    fn serves_snapshots_over_tcp() {
+        let tree = VirtualTree::mount(host("root", vec![host("child", vec![])]));
+        let mut server = DevtoolsServer::bind_tcp("127.0.0.1:0").unwrap();
+        let addr = match server.listener {
+            Listener::Tcp(ref listener) => listener.local_addr().unwrap(),
+            #[cfg(unix)]
+            Listener::Unix(_) => unreachable!(),
+        };
+
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut read = || {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                serde_json::from_str::<Value>(&line).unwrap()
+            };
+            let hello = read();
+            writer.write_all(b"{\"type\": \"snapshot\"}\n").unwrap();
+            (hello, read())
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !client.is_finished() {
+            assert!(Instant::now() < deadline, "the client got no answer");
+            assert!(server.poll(&tree).is_empty());
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (hello, snapshot) = client.join().unwrap();
+
+        assert_eq!(hello["type"], "hello");
+        assert_eq!(hello["protocol"], super::PROTOCOL_VERSION);
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["revision"], tree.revision());
+        let nodes = snapshot["nodes"].as_array().unwrap();
+        let elements = nodes
+            .iter()
+            .map(|node| node["element"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(elements, vec!["Widget(\"root\")", "Widget(\"child\")"]);
+        assert_eq!(nodes[0]["key"], snapshot["root"]);
+    }
+}
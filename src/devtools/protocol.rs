@@ -0,0 +1,227 @@
+use element::HostElement;
+use flat_tree::NodeKey;
+use reconciler::{InspectedNode, NodeKind, RenderReason, VirtualNode, VirtualTree};
+use serde_json::Value;
+use std::fmt;
+
+/// Version sent in the `hello` message. Bumped whenever a message
+/// changes shape.
+pub const PROTOCOL_VERSION: u64 = 2;
+
+pub enum ClientMessage<H: HostElement> {
+    Snapshot,
+    Select(NodeKey<VirtualNode<H>>),
+    Highlight(Option<NodeKey<VirtualNode<H>>>),
+}
+
+pub fn encode_key<H: HostElement>(key: NodeKey<VirtualNode<H>>) -> String {
+    format!("{}v{}", key.index(), key.generation())
+}
+
+fn decode_key<H: HostElement>(id: &str) -> Option<NodeKey<VirtualNode<H>>> {
+    let mut parts = id.splitn(2, 'v');
+    let index = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    Some(NodeKey::from_parts(index, generation))
+}
+
+fn decode_node<H: HostElement>(message: &Value) -> Result<Option<NodeKey<VirtualNode<H>>>, String> {
+    match message.get("node") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(id)) => decode_key(id)
+            .map(Some)
+            .ok_or_else(|| format!("malformed node id {:?}", id)),
+        Some(other) => Err(format!("expected a node id, got {}", other)),
+    }
+}
+
+pub fn decode<H: HostElement>(line: &str) -> Result<ClientMessage<H>, String> {
+    let message: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    match message.get("type").and_then(Value::as_str) {
+        Some("snapshot") => Ok(ClientMessage::Snapshot),
+        Some("select") => match decode_node(&message)? {
+            Some(key) => Ok(ClientMessage::Select(key)),
+            None => Err("select requires a node".to_owned()),
+        },
+        Some("highlight") => Ok(ClientMessage::Highlight(decode_node(&message)?)),
+        Some(other) => Err(format!("unknown message type {:?}", other)),
+        None => Err("message has no type".to_owned()),
+    }
+}
+
+fn encode_kind(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Host => "host",
+        NodeKind::Stateful => "stateful",
+        NodeKind::Fragment => "fragment",
+        NodeKind::Layer => "layer",
+    }
+}
+
+fn encode_render_reason(reason: RenderReason) -> &'static str {
+    match reason {
+        RenderReason::Mount => "mount",
+        RenderReason::StateUpdate => "state_update",
+        RenderReason::PropsChanged => "props_changed",
+        RenderReason::UnstableProps => "unstable_props",
+    }
+}
+
+pub fn encode_node<H>(node: &InspectedNode<H>) -> Value
+where
+    H: HostElement + fmt::Debug,
+{
+    json!({
+        "key": encode_key(node.key),
+        "parent": node.parent.map(encode_key),
+        "depth": node.depth,
+        "kind": encode_kind(node.kind),
+        "type_name": node.type_name,
+        "children": node.children.iter().map(|&key| encode_key(key)).collect::<Vec<_>>(),
+        "element": node.element.map(|element| format!("{:?}", element)),
+        "layer": node.layer.map(|layer| json!({
+            "name": layer.name,
+            "z_index": layer.z_index,
+        })),
+        "props": node.props,
+        "state": node.state,
+        "render_reason": node.render_reason.map(encode_render_reason),
+        "render_count": node.render_count,
+    })
+}
+
+pub fn hello<H: HostElement>(tree: &VirtualTree<H>) -> Value {
+    json!({
+        "type": "hello",
+        "protocol": PROTOCOL_VERSION,
+        "revision": tree.revision(),
+    })
+}
+
+pub fn snapshot<H>(tree: &VirtualTree<H>) -> Value
+where
+    H: HostElement + fmt::Debug,
+{
+    json!({
+        "type": "snapshot",
+        "revision": tree.revision(),
+        "root": tree.root().map(encode_key),
+        "nodes": tree.inspect().iter().map(encode_node).collect::<Vec<_>>(),
+    })
+}
+
+pub fn changed<H: HostElement>(tree: &VirtualTree<H>) -> Value {
+    json!({
+        "type": "changed",
+        "revision": tree.revision(),
+    })
+}
+
+pub fn selected<H>(node: &InspectedNode<H>) -> Value
+where
+    H: HostElement + fmt::Debug,
+{
+    json!({
+        "type": "selected",
+        "node": encode_node(node),
+    })
+}
+
+pub fn error(message: &str) -> Value {
+    json!({
+        "type": "error",
+        "message": message,
+    })
+}
+
+pub fn not_mounted<H: HostElement>(key: NodeKey<VirtualNode<H>>) -> Value {
+    error(&format!("node {} is not mounted", encode_key(key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_key, encode_key, ClientMessage};
+    use flat_tree::NodeKey;
+    use reconciler::tests::Widget;
+    use reconciler::VirtualNode;
+
+    fn decode_error(line: &str) -> String {
+        match decode::<Widget>(line) {
+            Err(message) => message,
+            Ok(_) => panic!("{} should be rejected", line),
+        }
+    }
+
+    #[test]
+    fn decodes_every_message() {
+        let key = NodeKey::from_parts(3, 1);
+        assert!(matches!(
+            decode::<Widget>(r#"{"type": "snapshot"}"#),
+            Ok(ClientMessage::Snapshot)
+        ));
+        match decode::<Widget>(r#"{"type": "select", "node": "3v1"}"#) {
+            Ok(ClientMessage::Select(selected)) => assert_eq!(selected, key),
+            _ => panic!("expected a select message"),
+        }
+        match decode::<Widget>(r#"{"type": "highlight", "node": "3v1"}"#) {
+            Ok(ClientMessage::Highlight(highlighted)) => assert_eq!(highlighted, Some(key)),
+            _ => panic!("expected a highlight message"),
+        }
+        for line in &[
+            r#"{"type": "highlight", "node": null}"#,
+            r#"{"type": "highlight"}"#,
+        ] {
+            match decode::<Widget>(line) {
+                Ok(ClientMessage::Highlight(None)) => (),
+                _ => panic!("{} should clear the highlight", line),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        assert_eq!(decode_error(r#"{"node": "3v1"}"#), "message has no type");
+        assert_eq!(decode_error(r#"{"type": 7}"#), "message has no type");
+        assert_eq!(
+            decode_error(r#"{"type": "reload"}"#),
+            "unknown message type \"reload\""
+        );
+        assert_eq!(
+            decode_error(r#"{"type": "select"}"#),
+            "select requires a node"
+        );
+        assert_eq!(
+            decode_error(r#"{"type": "select", "node": 3}"#),
+            "expected a node id, got 3"
+        );
+        assert_eq!(
+            decode_error(r#"{"type": "select", "node": "3"}"#),
+            "malformed node id \"3\""
+        );
+        decode_error("");
+        decode_error("not json");
+        decode_error(r#"["snapshot"]"#);
+        decode_error(r#"{"type": "snapshot""#);
+    }
+
+    #[test]
+    fn decodes_only_well_formed_keys() {
+        let key = NodeKey::<VirtualNode<Widget>>::from_parts(12, 4);
+        assert_eq!(decode_key(&encode_key(key)), Some(key));
+        for id in &[
+            "",
+            "v",
+            "3",
+            "3v",
+            "v1",
+            "-1v0",
+            "3v-1",
+            "3v0v1",
+            " 3v0",
+            "3x0",
+            "3v99999999999",
+        ] {
+            assert_eq!(decode_key::<Widget>(id), None, "{:?}", id);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use component::Component;
 use reconciler::StatefulElementWrapper;
+use std::convert::Infallible;
 use std::marker::PhantomData;
 
 pub trait DomNode<'a>
@@ -11,8 +12,50 @@ where
     fn new_dom_node(h: &'a Self::Widget, children: Vec<Self>) -> Self;
 }
 
+/// A `DomNode` whose construction can fail, for hosts that load
+/// resources such as fonts while building nodes. Every `DomNode` is a
+/// `TryDomNode` that never fails.
+pub trait TryDomNode<'a>
+where
+    Self: 'a + Sized,
+{
+    type Widget;
+    type Error;
+
+    fn try_new_dom_node(h: &'a Self::Widget, children: Vec<Self>) -> Result<Self, Self::Error>;
+}
+
+impl<'a, Dom> TryDomNode<'a> for Dom
+where
+    Dom: DomNode<'a>,
+{
+    type Widget = Dom::Widget;
+    type Error = Infallible;
+
+    fn try_new_dom_node(h: &'a Self::Widget, children: Vec<Self>) -> Result<Self, Infallible> {
+        Ok(Dom::new_dom_node(h, children))
+    }
+}
+
 pub trait HostElement: 'static + Sized + PartialEq {}
 
+/// A named surface drawn above or below the main tree, such as one for
+/// tooltips or modals. `VirtualTree::render_layers` draws layers in
+/// ascending `z_index`; the main tree is `Layer::BASE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Layer {
+    pub name: &'static str,
+    pub z_index: i32,
+}
+
+impl Layer {
+    pub const BASE: Layer = Layer::new("base", 0);
+
+    pub const fn new(name: &'static str, z_index: i32) -> Layer {
+        Layer { name, z_index }
+    }
+}
+
 pub enum Element<H: HostElement> {
     Host {
         element: H,
@@ -20,6 +63,12 @@ pub enum Element<H: HostElement> {
     },
     Stateful(Box<dyn StatefulElementWrapper<H>>),
     Fragment(Vec<Element<H>>),
+    /// Children that stay owned by the component declaring them but are
+    /// drawn in `layer` instead of inside their parent host node.
+    Layer {
+        layer: Layer,
+        children: Vec<Element<H>>,
+    },
 }
 
 pub struct StatefulElement<H: HostElement, Class: Component<H>> {
@@ -42,6 +91,10 @@ impl<H: HostElement> Element<H> {
         Element::Fragment(children)
     }
 
+    pub fn new_layer(layer: Layer, children: Vec<Element<H>>) -> Element<H> {
+        Element::Layer { layer, children }
+    }
+
     pub fn new_functional<F, Props>(
         _func: F,
         _props: Props,
@@ -64,3 +117,9 @@ impl<H: HostElement> Element<H> {
         }))
     }
 }
+
+impl<H: HostElement> From<Vec<Element<H>>> for Element<H> {
+    fn from(children: Vec<Element<H>>) -> Element<H> {
+        Element::new_fragment(children)
+    }
+}
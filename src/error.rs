@@ -0,0 +1,192 @@
+use reconciler::ComponentStack;
+use std::error::Error;
+use std::fmt;
+
+/// Why a `VirtualTree` operation failed. The tree is left usable, so
+/// callers can log the error and carry on.
+#[derive(Debug)]
+pub enum ReactError {
+    /// A `NodeKey` that doesn't refer to a mounted node.
+    StaleKey {
+        index: usize,
+        generation: u32,
+        component_stack: ComponentStack,
+    },
+    /// The tree was found in a state the reconciler never leaves it in,
+    /// for example a node being reached while it is itself updating.
+    Invariant {
+        message: String,
+        component_stack: ComponentStack,
+    },
+    /// `VirtualTree::flush` reached its flush limit while these
+    /// components were still scheduling updates. Their pending updates
+    /// stay queued for the next flush.
+    FlushLimit {
+        passes: usize,
+        components: Vec<&'static str>,
+    },
+    /// `VirtualTree::render` found more than one top-level host node.
+    /// `VirtualTree::render_all` accepts any number of them.
+    MultipleRoots { count: usize },
+    /// A `RootKey` whose tree was unmounted from its `Roots`.
+    StaleRoot { id: usize },
+    /// The host failed to create a node.
+    Host {
+        error: Box<dyn Error + Send + Sync>,
+        component_stack: ComponentStack,
+    },
+}
+
+impl ReactError {
+    /// The components the reconciler was inside of when the error
+    /// happened, if it was inside any.
+    pub fn component_stack(&self) -> Option<&ComponentStack> {
+        let stack = match *self {
+            ReactError::StaleKey {
+                ref component_stack,
+                ..
+            }
+            | ReactError::Invariant {
+                ref component_stack,
+                ..
+            }
+            | ReactError::Host {
+                ref component_stack,
+                ..
+            } => component_stack,
+            _ => return None,
+        };
+        if stack.is_empty() {
+            None
+        } else {
+            Some(stack)
+        }
+    }
+}
+
+pub fn stale_key(index: usize, generation: u32) -> ReactError {
+    ReactError::StaleKey {
+        index,
+        generation,
+        component_stack: ComponentStack::current(),
+    }
+}
+
+pub fn invariant<S: Into<String>>(message: S) -> ReactError {
+    ReactError::Invariant {
+        message: message.into(),
+        component_stack: ComponentStack::current(),
+    }
+}
+
+impl fmt::Display for ReactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReactError::StaleKey {
+                index, generation, ..
+            } => write!(f, "stale NodeKey {}v{}", index, generation)?,
+            ReactError::Invariant { ref message, .. } => write!(f, "{}", message)?,
+            ReactError::FlushLimit {
+                passes,
+                ref components,
+            } => write!(
+                f,
+                "flush did not settle after {} passes, updates are still being scheduled by: {}",
+                passes,
+                components.join(", ")
+            )?,
+            ReactError::MultipleRoots { count } => write!(
+                f,
+                "the tree rendered {} top-level host nodes, expected at most one",
+                count
+            )?,
+            ReactError::StaleRoot { id } => write!(f, "stale RootKey {}", id)?,
+            ReactError::Host { ref error, .. } => write!(f, "host error: {}", error)?,
+        }
+        if let Some(stack) = self.component_stack() {
+            write!(f, " (in {})", stack)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ReactError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ReactError::Host { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invariant, stale_key, ReactError};
+    use component::{Component, RenderContext};
+    use element::{Element, TryDomNode};
+    use reconciler::tests::{log, take_log, Chain, Widget};
+    use reconciler::VirtualTree;
+    use std::any::type_name;
+
+    /// Host that fails to create `Widget("leaf")`.
+    struct NoLeaves;
+
+    impl<'a> TryDomNode<'a> for NoLeaves {
+        type Widget = Widget;
+        type Error = String;
+
+        fn try_new_dom_node(widget: &Widget, _children: Vec<Self>) -> Result<Self, String> {
+            match *widget {
+                Widget("leaf") => Err("no leaves".to_owned()),
+                _ => Ok(NoLeaves),
+            }
+        }
+    }
+
+    #[test]
+    fn host_errors_name_the_components_above_the_node() {
+        let tree = VirtualTree::mount(Element::new_stateful::<Chain>(1));
+        let err = tree.try_render::<NoLeaves>().err().unwrap();
+        let chain = type_name::<Chain>();
+        assert_eq!(err.component_stack().unwrap().components(), [chain, chain]);
+        assert_eq!(
+            err.to_string(),
+            format!("host error: no leaves (in {} > {})", chain, chain)
+        );
+    }
+
+    /// Logs an invariant error raised while it renders.
+    struct Failing;
+
+    impl Component<Widget> for Failing {
+        type Props = ();
+        type State = ();
+
+        fn create(_props: &()) -> (Self, ()) {
+            (Failing, ())
+        }
+
+        fn render(&self, _ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            log(invariant("render failed").to_string());
+            Element::new_fragment(vec![])
+        }
+    }
+
+    #[test]
+    fn errors_raised_inside_the_reconciler_have_a_stack() {
+        VirtualTree::mount(Element::new_stateful::<Failing>(()));
+        assert_eq!(
+            take_log(),
+            vec![format!("render failed (in {})", type_name::<Failing>())]
+        );
+
+        let err = stale_key(1, 2);
+        assert!(err.component_stack().is_none());
+        assert_eq!(err.to_string(), "stale NodeKey 1v2");
+        let err = ReactError::FlushLimit {
+            passes: 3,
+            components: vec!["A", "B"],
+        };
+        assert!(err.component_stack().is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use super::{FlatTree, GetNodeChildren, NodeKey};
+use error::ReactError;
+use std::collections::VecDeque;
+
+/// The order in which `FlatTree::iter` and `FlatTree::visit_mut` visit
+/// nodes. Siblings are always visited first to last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Parents before their children.
+    PreOrder,
+    /// Children before their parents, the same order `recurse` uses.
+    PostOrder,
+    /// Level by level, starting at the root.
+    BreadthFirst,
+}
+
+struct Pending<Item> {
+    key: NodeKey<Item>,
+    depth: usize,
+    expanded: bool,
+}
+
+/// Nodes that have been discovered but not yet visited.
+struct Frontier<Item> {
+    order: TraversalOrder,
+    pending: VecDeque<Pending<Item>>,
+}
+
+impl<Item> Frontier<Item>
+where
+    Item: GetNodeChildren,
+{
+    fn new(order: TraversalOrder, root: Option<NodeKey<Item>>) -> Frontier<Item> {
+        Frontier {
+            order,
+            pending: root
+                .map(|key| Pending {
+                    key,
+                    depth: 0,
+                    expanded: false,
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn push_children(&mut self, tree: &FlatTree<Item>, key: NodeKey<Item>, depth: usize) {
+        let children = tree.items.get(key).get_children().children.iter();
+        let children = children.map(|&key| Pending {
+            key,
+            depth: depth + 1,
+            expanded: false,
+        });
+        match self.order {
+            TraversalOrder::BreadthFirst => self.pending.extend(children),
+            // Pushed in reverse so the first child is popped first.
+            _ => {
+                let children = children.collect::<Vec<_>>();
+                self.pending.extend(children.into_iter().rev());
+            }
+        }
+    }
+
+    fn next(&mut self, tree: &FlatTree<Item>) -> Option<(NodeKey<Item>, usize)> {
+        loop {
+            let node = match self.order {
+                TraversalOrder::BreadthFirst => self.pending.pop_front()?,
+                _ => self.pending.pop_back()?,
+            };
+
+            if self.order == TraversalOrder::PostOrder && !node.expanded {
+                let (key, depth) = (node.key, node.depth);
+                self.pending.push_back(Pending {
+                    expanded: true,
+                    ..node
+                });
+                self.push_children(tree, key, depth);
+                continue;
+            }
+
+            if self.order != TraversalOrder::PostOrder {
+                self.push_children(tree, node.key, node.depth);
+            }
+            return Some((node.key, node.depth));
+        }
+    }
+}
+
+/// Lazy iterator over the nodes of a `FlatTree`, created by
+/// `FlatTree::iter` and `FlatTree::iter_subtree`. Yields each node's
+/// key, the node, and its depth relative to where iteration started.
+pub struct Iter<'a, Item: 'a> {
+    tree: &'a FlatTree<Item>,
+    frontier: Frontier<Item>,
+}
+
+impl<'a, Item> Iterator for Iter<'a, Item>
+where
+    Item: GetNodeChildren,
+{
+    type Item = (NodeKey<Item>, &'a Item, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, depth) = self.frontier.next(self.tree)?;
+        Some((key, self.tree.items.get(key), depth))
+    }
+}
+
+impl<Item> FlatTree<Item>
+where
+    Item: GetNodeChildren,
+{
+    pub fn iter(&self, order: TraversalOrder) -> Iter<'_, Item> {
+        Iter {
+            tree: self,
+            frontier: Frontier::new(order, self.root),
+        }
+    }
+
+    /// Like `iter`, starting at `key`. Fails if `key` is stale.
+    pub fn iter_subtree(
+        &self,
+        key: NodeKey<Item>,
+        order: TraversalOrder,
+    ) -> Result<Iter<'_, Item>, ReactError> {
+        self.items.check(key)?;
+        Ok(Iter {
+            tree: self,
+            frontier: Frontier::new(order, Some(key)),
+        })
+    }
+
+    /// Calls `visit` with mutable access to every node. In every order,
+    /// a node's children are read before it is visited, so changes that
+    /// `visit` makes to a node's children don't change what's visited.
+    pub fn visit_mut<Func>(&mut self, order: TraversalOrder, visit: Func)
+    where
+        Func: FnMut(NodeKey<Item>, &mut Item, usize),
+    {
+        let root = self.root;
+        self.visit_from_mut(Frontier::new(order, root), visit);
+    }
+
+    /// Like `visit_mut`, starting at `key`. Fails if `key` is stale.
+    pub fn visit_subtree_mut<Func>(
+        &mut self,
+        key: NodeKey<Item>,
+        order: TraversalOrder,
+        visit: Func,
+    ) -> Result<(), ReactError>
+    where
+        Func: FnMut(NodeKey<Item>, &mut Item, usize),
+    {
+        self.items.check(key)?;
+        self.visit_from_mut(Frontier::new(order, Some(key)), visit);
+        Ok(())
+    }
+
+    fn visit_from_mut<Func>(&mut self, mut frontier: Frontier<Item>, mut visit: Func)
+    where
+        Func: FnMut(NodeKey<Item>, &mut Item, usize),
+    {
+        while let Some((key, depth)) = frontier.next(self) {
+            visit(key, self.items.get_mut(key), depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraversalOrder;
+    use flat_tree::tests::{build, post_order, Node, Spec};
+    use flat_tree::{FlatTree, GetNodeChildren};
+
+    fn values(iter: super::Iter<'_, Node>) -> Vec<(u32, usize)> {
+        iter.map(|(_, node, depth)| (node.value, depth)).collect()
+    }
+
+    #[test]
+    fn iterates_in_each_order() {
+        let tree = build(&mut vec![]);
+        assert_eq!(
+            values(tree.iter(TraversalOrder::PreOrder)),
+            vec![(1, 0), (2, 1), (3, 2), (4, 2), (5, 1)]
+        );
+        assert_eq!(
+            values(tree.iter(TraversalOrder::PostOrder)),
+            vec![(3, 2), (4, 2), (2, 1), (5, 1), (1, 0)]
+        );
+        assert_eq!(
+            values(tree.iter(TraversalOrder::BreadthFirst)),
+            vec![(1, 0), (2, 1), (5, 1), (3, 2), (4, 2)]
+        );
+    }
+
+    #[test]
+    fn post_order_matches_recurse() {
+        let tree = build(&mut vec![]);
+        let iterated = tree
+            .iter(TraversalOrder::PostOrder)
+            .map(|(_, node, _)| node.value)
+            .collect::<Vec<_>>();
+        assert_eq!(iterated, post_order(&tree));
+    }
+
+    #[test]
+    fn keys_match_the_nodes() {
+        let tree = build(&mut vec![]);
+        for (key, node, _) in tree.iter(TraversalOrder::PreOrder) {
+            assert_eq!(tree.get(key).unwrap().value, node.value);
+        }
+    }
+
+    #[test]
+    fn empty_trees_yield_nothing() {
+        let tree = FlatTree::<Node>::new();
+        assert_eq!(tree.iter(TraversalOrder::PreOrder).count(), 0);
+        assert_eq!(tree.iter(TraversalOrder::PostOrder).count(), 0);
+        assert_eq!(tree.iter(TraversalOrder::BreadthFirst).count(), 0);
+    }
+
+    #[test]
+    fn iterates_subtrees_with_relative_depths() {
+        let tree = build(&mut vec![]);
+        let two = tree.get_children(tree.root().unwrap()).unwrap()[0];
+        assert_eq!(
+            values(tree.iter_subtree(two, TraversalOrder::PreOrder).unwrap()),
+            vec![(2, 0), (3, 1), (4, 1)]
+        );
+        assert_eq!(
+            values(tree.iter_subtree(two, TraversalOrder::PostOrder).unwrap()),
+            vec![(3, 1), (4, 1), (2, 0)]
+        );
+        assert_eq!(
+            values(
+                tree.iter_subtree(two, TraversalOrder::BreadthFirst)
+                    .unwrap()
+            ),
+            vec![(2, 0), (3, 1), (4, 1)]
+        );
+
+        let four = tree.get_children(two).unwrap()[1];
+        assert_eq!(
+            values(tree.iter_subtree(four, TraversalOrder::PostOrder).unwrap()),
+            vec![(4, 0)]
+        );
+    }
+
+    #[test]
+    fn visits_mutably_in_order() {
+        let mut tree = build(&mut vec![]);
+        let mut visited = vec![];
+        tree.visit_mut(TraversalOrder::BreadthFirst, |_, node, depth| {
+            visited.push((node.value, depth));
+            node.value *= 10;
+        });
+        assert_eq!(visited, vec![(1, 0), (2, 1), (5, 1), (3, 2), (4, 2)]);
+        assert_eq!(post_order(&tree), vec![30, 40, 20, 50, 10]);
+
+        let two = tree.get_children(tree.root().unwrap()).unwrap()[0];
+        let mut visited = vec![];
+        tree.visit_subtree_mut(two, TraversalOrder::PostOrder, |_, node, depth| {
+            visited.push((node.value, depth));
+            node.value += 1;
+        })
+        .unwrap();
+        assert_eq!(visited, vec![(30, 1), (40, 1), (20, 0)]);
+        assert_eq!(post_order(&tree), vec![31, 41, 21, 50, 10]);
+    }
+
+    #[test]
+    fn visits_the_children_a_node_had_before_its_visit() {
+        for &order in &[
+            TraversalOrder::PreOrder,
+            TraversalOrder::PostOrder,
+            TraversalOrder::BreadthFirst,
+        ] {
+            let mut tree = build(&mut vec![]);
+            let mut visited = vec![];
+            tree.visit_mut(order, |_, node, _| {
+                visited.push(node.value);
+                node.get_children_mut().children.clear();
+            });
+            visited.sort();
+            assert_eq!(visited, vec![1, 2, 3, 4, 5], "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn stale_subtree_keys_are_errors() {
+        let mut tree = build(&mut vec![]);
+        let five = tree.get_children(tree.root().unwrap()).unwrap()[1];
+        tree.update_tree(
+            Spec(1, vec![Spec(2, vec![])]),
+            &mut |_, _| panic!("nothing should be mounted"),
+            &mut |node: Node, Spec(_, children), _| (node, Some(children)),
+            &mut |_, _| (),
+            &mut |_, _| (),
+        )
+        .unwrap();
+
+        assert!(tree.iter_subtree(five, TraversalOrder::PreOrder).is_err());
+        assert!(tree
+            .visit_subtree_mut(five, TraversalOrder::PreOrder, |_, _, _| {
+                panic!("nothing should be visited")
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,1088 @@
+use error::{invariant, stale_key, ReactError};
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::vec;
+
+mod iter;
+
+pub use self::iter::{Iter, TraversalOrder};
+
+/// Handle to a node in a `FlatTree`. Keys stay valid until the node is
+/// removed; after that the slot's generation no longer matches and the
+/// key is reported as stale, even if the slot has been reused.
+pub struct NodeKey<Item> {
+    index: usize,
+    generation: u32,
+    _phantom: PhantomData<Item>,
+}
+
+impl<Item> Clone for NodeKey<Item> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Item> Copy for NodeKey<Item> {}
+
+impl<Item> NodeKey<Item> {
+    fn new(index: usize, generation: u32) -> NodeKey<Item> {
+        NodeKey {
+            index,
+            generation,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Rebuilds a key from the parts returned by `index` and
+    /// `generation`, for example after sending it to another process.
+    /// Keys that don't refer to a live node are treated as stale.
+    pub fn from_parts(index: usize, generation: u32) -> NodeKey<Item> {
+        NodeKey::new(index, generation)
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<Item> PartialEq for NodeKey<Item> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<Item> Eq for NodeKey<Item> {}
+
+impl<Item> fmt::Debug for NodeKey<Item> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NodeKey({}v{})", self.index, self.generation)
+    }
+}
+
+impl<Item> Hash for NodeKey<Item> {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+pub struct NodeChildren<Item> {
+    children: Vec<NodeKey<Item>>,
+}
+
+impl<Item> NodeChildren<Item> {
+    pub fn new() -> NodeChildren<Item> {
+        NodeChildren { children: vec![] }
+    }
+}
+
+impl<Item> Default for NodeChildren<Item> {
+    fn default() -> Self {
+        NodeChildren::new()
+    }
+}
+
+pub trait GetNodeChildren: Sized {
+    fn get_children(&self) -> &NodeChildren<Self>;
+    fn get_children_mut(&mut self) -> &mut NodeChildren<Self>;
+}
+
+enum SlotState<Item> {
+    Free,
+    /// The key has been handed out, but the item is being built or
+    /// updated and isn't stored yet.
+    Reserved,
+    Occupied(Item),
+}
+
+struct Slot<Item> {
+    generation: u32,
+    parent: Option<NodeKey<Item>>,
+    state: SlotState<Item>,
+}
+
+/// Generational arena backing `FlatTree`. Removed slots are recycled
+/// with a bumped generation so that old keys can't alias new nodes.
+struct Arena<Item> {
+    slots: Vec<Slot<Item>>,
+    free: Vec<usize>,
+}
+
+impl<Item> Arena<Item> {
+    fn new() -> Arena<Item> {
+        Arena {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    fn slot(&self, key: NodeKey<Item>) -> Option<&Slot<Item>> {
+        self.slots
+            .get(key.index)
+            .filter(|slot| slot.generation == key.generation)
+    }
+
+    fn slot_mut(&mut self, key: NodeKey<Item>) -> &mut Slot<Item> {
+        match self.slots.get_mut(key.index) {
+            Some(slot) if slot.generation == key.generation => slot,
+            _ => panic!("stale NodeKey {}v{}", key.index, key.generation),
+        }
+    }
+
+    fn reserve(&mut self, parent: Option<NodeKey<Item>>) -> NodeKey<Item> {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.parent = parent;
+            slot.state = SlotState::Reserved;
+            NodeKey::new(index, slot.generation)
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                parent,
+                state: SlotState::Reserved,
+            });
+            NodeKey::new(self.slots.len() - 1, 0)
+        }
+    }
+
+    fn contains(&self, key: NodeKey<Item>) -> bool {
+        matches!(
+            self.slot(key),
+            Some(Slot {
+                state: SlotState::Occupied(_),
+                ..
+            })
+        )
+    }
+
+    /// Stores an item under a key from `reserve` or `take`.
+    fn insert(&mut self, key: NodeKey<Item>, item: Item) {
+        let slot = self.slot_mut(key);
+        match slot.state {
+            SlotState::Reserved => slot.state = SlotState::Occupied(item),
+            _ => panic!("NodeKey {}v{} is not reserved", key.index, key.generation),
+        }
+    }
+
+    /// Moves an item out while keeping its slot, so it can be put back
+    /// with `insert` under the same key.
+    fn take(&mut self, key: NodeKey<Item>) -> Item {
+        let slot = self.slot_mut(key);
+        match ::std::mem::replace(&mut slot.state, SlotState::Reserved) {
+            SlotState::Occupied(item) => item,
+            _ => panic!("NodeKey {}v{} is not occupied", key.index, key.generation),
+        }
+    }
+
+    fn remove(&mut self, key: NodeKey<Item>) -> Item {
+        let item = self.take(key);
+        let slot = &mut self.slots[key.index];
+        slot.parent = None;
+        slot.state = SlotState::Free;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index);
+        item
+    }
+
+    /// Fails unless `key` refers to a stored item.
+    fn check(&self, key: NodeKey<Item>) -> Result<(), ReactError> {
+        match self.slot(key) {
+            Some(Slot {
+                state: SlotState::Occupied(_),
+                ..
+            }) => Ok(()),
+            Some(Slot {
+                state: SlotState::Reserved,
+                ..
+            }) => Err(invariant(format!(
+                "NodeKey {}v{} was reached while it is being built or updated",
+                key.index, key.generation
+            ))),
+            _ => Err(stale_key(key.index, key.generation)),
+        }
+    }
+
+    /// The parent of a stored or reserved item.
+    fn parent(&self, key: NodeKey<Item>) -> Result<Option<NodeKey<Item>>, ReactError> {
+        match self.slot(key) {
+            Some(Slot {
+                state: SlotState::Free,
+                ..
+            })
+            | None => Err(stale_key(key.index, key.generation)),
+            Some(slot) => Ok(slot.parent),
+        }
+    }
+
+    fn get(&self, key: NodeKey<Item>) -> &Item {
+        match self.slot(key) {
+            Some(Slot {
+                state: SlotState::Occupied(ref item),
+                ..
+            }) => item,
+            _ => panic!("stale NodeKey {}v{}", key.index, key.generation),
+        }
+    }
+
+    fn get_mut(&mut self, key: NodeKey<Item>) -> &mut Item {
+        match self.slot_mut(key).state {
+            SlotState::Occupied(ref mut item) => item,
+            _ => panic!("NodeKey {}v{} is not occupied", key.index, key.generation),
+        }
+    }
+}
+
+// The traversals below keep explicit stacks of these frames instead of
+// recursing, so that very deep trees can't overflow the call stack.
+
+struct BuildFrame<Item, Value> {
+    key: NodeKey<Item>,
+    item: Item,
+    pending: vec::IntoIter<Value>,
+    children: Vec<NodeKey<Item>>,
+}
+
+struct FoldFrame<Key, Item, Pending, Res> {
+    key: Key,
+    item: Item,
+    pending: Pending,
+    results: Vec<Res>,
+}
+
+struct UpdateFrame<Item, Value> {
+    key: NodeKey<Item>,
+    item: Item,
+    pending: vec::IntoIter<(Option<NodeKey<Item>>, Option<Value>)>,
+    children: Vec<NodeKey<Item>>,
+}
+
+pub struct FlatTree<Item> {
+    items: Arena<Item>,
+    root: Option<NodeKey<Item>>,
+}
+
+impl<Item> FlatTree<Item>
+where
+    Item: GetNodeChildren,
+{
+    pub fn new() -> FlatTree<Item> {
+        FlatTree {
+            items: Arena::new(),
+            root: None,
+        }
+    }
+
+    fn reserve(&mut self, parent: Option<NodeKey<Item>>) -> NodeKey<Item> {
+        self.items.reserve(parent)
+    }
+
+    fn insert(&mut self, key: NodeKey<Item>, item: Item) {
+        self.items.insert(key, item);
+    }
+
+    fn build_inner<Value, Func, Finish>(
+        &mut self,
+        parent: Option<NodeKey<Item>>,
+        root: Value,
+        create_node: &mut Func,
+        finish_node: &mut Finish,
+    ) -> NodeKey<Item>
+    where
+        Func: FnMut(Value, NodeKey<Item>) -> (Item, Vec<Value>),
+        Finish: FnMut(&Item, NodeKey<Item>),
+    {
+        let mut stack = vec![];
+        let mut next = Some(root);
+
+        loop {
+            if let Some(value) = next.take() {
+                let parent = stack
+                    .last()
+                    .map(|frame: &BuildFrame<_, _>| frame.key)
+                    .or(parent);
+                let key = self.reserve(parent);
+                let (item, children) = create_node(value, key);
+                stack.push(BuildFrame {
+                    key,
+                    item,
+                    pending: children.into_iter(),
+                    children: vec![],
+                });
+            }
+
+            let frame = stack.last_mut().unwrap();
+            next = frame.pending.next();
+            if next.is_some() {
+                continue;
+            }
+
+            // All of this node's children are built, so it can be stored.
+            let BuildFrame {
+                key,
+                mut item,
+                children,
+                ..
+            } = stack.pop().unwrap();
+            item.get_children_mut().children = children;
+            finish_node(&item, key);
+            self.insert(key, item);
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(key),
+                None => return key,
+            }
+        }
+    }
+
+    /// Builds a tree from `root`. `create_node` is called on each value
+    /// in pre-order, and `finish_node` on each node in post-order, once
+    /// all of its children have been built.
+    pub fn build<Value, Func, Finish>(
+        root: Value,
+        mut create_node: Func,
+        mut finish_node: Finish,
+    ) -> FlatTree<Item>
+    where
+        Func: FnMut(Value, NodeKey<Item>) -> (Item, Vec<Value>),
+        Finish: FnMut(&Item, NodeKey<Item>),
+    {
+        let mut tree = FlatTree::new();
+
+        tree.root = Some(tree.build_inner(None, root, &mut create_node, &mut finish_node));
+
+        tree
+    }
+
+    fn unbuild_inner<Func, Res>(&mut self, key: NodeKey<Item>, take_item: &mut Func) -> Res
+    where
+        Func: FnMut(Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        let mut stack = vec![];
+        let mut next = Some(key);
+
+        loop {
+            if let Some(key) = next.take() {
+                let item = self.items.remove(key);
+                let pending = item.get_children().children.clone().into_iter();
+                stack.push(FoldFrame {
+                    key,
+                    item,
+                    pending,
+                    results: vec![],
+                });
+            }
+
+            let frame = stack.last_mut().unwrap();
+            next = frame.pending.next();
+            if next.is_some() {
+                continue;
+            }
+
+            let FoldFrame {
+                key, item, results, ..
+            } = stack.pop().unwrap();
+            let result = take_item(item, results, key);
+
+            match stack.last_mut() {
+                Some(parent) => parent.results.push(result),
+                None => return result,
+            }
+        }
+    }
+
+    pub fn unbuild<Func, Res>(mut self, mut take_item: Func) -> Option<Res>
+    where
+        Func: FnMut(Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        if let Some(root) = self.root {
+            Some(self.unbuild_inner(root, &mut take_item))
+        } else {
+            None
+        }
+    }
+
+    fn recurse_inner<'a, Func, Res>(&'a self, root: NodeKey<Item>, map_item: &mut Func) -> Res
+    where
+        Func: FnMut(&'a Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        let mut stack = vec![];
+        let mut next = Some(root);
+
+        loop {
+            if let Some(key) = next.take() {
+                let item = self.items.get(key);
+                stack.push(FoldFrame {
+                    key,
+                    item,
+                    pending: item.get_children().children.iter(),
+                    results: vec![],
+                });
+            }
+
+            let frame = stack.last_mut().unwrap();
+            next = frame.pending.next().cloned();
+            if next.is_some() {
+                continue;
+            }
+
+            let FoldFrame {
+                key, item, results, ..
+            } = stack.pop().unwrap();
+            let result = map_item(item, results, key);
+
+            match stack.last_mut() {
+                Some(parent) => parent.results.push(result),
+                None => return result,
+            }
+        }
+    }
+
+    pub fn recurse<'a, Func, Res>(&'a self, mut map_item: Func) -> Option<Res>
+    where
+        Func: FnMut(&'a Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        if let Some(root) = self.root {
+            Some(self.recurse_inner(root, &mut map_item))
+        } else {
+            None
+        }
+    }
+
+    fn recurse_inner_mut<Func, Res>(&mut self, index: NodeKey<Item>, map_item: &mut Func) -> Res
+    where
+        Func: FnMut(&mut Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        let mut stack = vec![];
+        let mut next = Some(index);
+
+        loop {
+            if let Some(key) = next.take() {
+                let pending = self.items.get(key).get_children().children.clone();
+                stack.push(FoldFrame {
+                    key,
+                    item: (),
+                    pending: pending.into_iter(),
+                    results: vec![],
+                });
+            }
+
+            let frame = stack.last_mut().unwrap();
+            next = frame.pending.next();
+            if next.is_some() {
+                continue;
+            }
+
+            let FoldFrame { key, results, .. } = stack.pop().unwrap();
+            let result = map_item(self.items.get_mut(key), results, key);
+
+            match stack.last_mut() {
+                Some(parent) => parent.results.push(result),
+                None => return result,
+            }
+        }
+    }
+
+    pub fn recurse_mut<Func, Res>(&mut self, mut map_item: Func) -> Option<Res>
+    where
+        Func: FnMut(&mut Item, Vec<Res>, NodeKey<Item>) -> Res,
+    {
+        if let Some(root) = self.root {
+            Some(self.recurse_inner_mut(root, &mut map_item))
+        } else {
+            None
+        }
+    }
+
+    /// Updates the subtree at `item_key` to match `value`. Nodes are
+    /// mounted or updated in pre-order, and every node that was mounted
+    /// or updated is passed to `finish_item` in post-order, once its own
+    /// subtree is done.
+    pub fn update_subtree<Value, MountItem, UpdateItem, UnmountItem, FinishItem>(
+        &mut self,
+        item_key: NodeKey<Item>,
+        value: Value,
+        mount_item: &mut MountItem,
+        update_item: &mut UpdateItem,
+        unmount_item: &mut UnmountItem,
+        finish_item: &mut FinishItem,
+    ) -> Result<NodeKey<Item>, ReactError>
+    where
+        MountItem: FnMut(Value, NodeKey<Item>) -> (Item, Vec<Value>),
+        UpdateItem: FnMut(Item, Value, NodeKey<Item>) -> (Item, Option<Vec<Value>>),
+        UnmountItem: FnMut(Item, NodeKey<Item>),
+        FinishItem: FnMut(&Item, NodeKey<Item>),
+    {
+        // Every other key reached below comes from the tree itself.
+        self.items.check(item_key)?;
+
+        let mut stack = vec![];
+        let mut next = Some((item_key, value));
+
+        loop {
+            if let Some((key, value)) = next.take() {
+                let mut item = self.items.take(key);
+                let previous_children = item
+                    .get_children_mut()
+                    .children
+                    .drain(..)
+                    .collect::<Vec<NodeKey<Item>>>();
+
+                let (item, child_values) = update_item(item, value, key);
+
+                // Old children are paired with new values by position.
+                // Leftover values are mounted and leftover children are
+                // unmounted.
+                let (pending, children) = match child_values {
+                    Some(child_values) => {
+                        let len = previous_children.len().max(child_values.len());
+                        let mut previous_children = previous_children.into_iter();
+                        let mut child_values = child_values.into_iter();
+                        let pending = (0..len)
+                            .map(|_| (previous_children.next(), child_values.next()))
+                            .collect::<Vec<_>>();
+                        (pending, vec![])
+                    }
+                    None => (vec![], previous_children),
+                };
+
+                stack.push(UpdateFrame {
+                    key,
+                    item,
+                    pending: pending.into_iter(),
+                    children,
+                });
+            }
+
+            let frame = stack.last_mut().unwrap();
+            match frame.pending.next() {
+                // Update
+                Some((Some(child_index), Some(child_value))) => {
+                    next = Some((child_index, child_value));
+                    continue;
+                }
+                // Mount
+                Some((None, Some(child_value))) => {
+                    let frame = stack.last_mut().unwrap();
+                    let child_index =
+                        self.build_inner(Some(frame.key), child_value, mount_item, finish_item);
+                    frame.children.push(child_index);
+                    continue;
+                }
+                // Unmount
+                Some((Some(child_index), None)) => {
+                    self.unbuild_inner(child_index, &mut |item, _, key| unmount_item(item, key));
+                    continue;
+                }
+                // Unreachable
+                Some((None, None)) => continue,
+                None => (),
+            }
+
+            let UpdateFrame {
+                key,
+                mut item,
+                children,
+                ..
+            } = stack.pop().unwrap();
+            item.get_children_mut().children = children;
+            finish_item(&item, key);
+            self.insert(key, item);
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(key),
+                None => return Ok(key),
+            }
+        }
+    }
+
+    pub fn update_tree<Value, MountItem, UpdateItem, UnmountItem, FinishItem>(
+        &mut self,
+        value: Value,
+        mount_item: &mut MountItem,
+        update_item: &mut UpdateItem,
+        unmount_item: &mut UnmountItem,
+        finish_item: &mut FinishItem,
+    ) -> Result<(), ReactError>
+    where
+        MountItem: FnMut(Value, NodeKey<Item>) -> (Item, Vec<Value>),
+        UpdateItem: FnMut(Item, Value, NodeKey<Item>) -> (Item, Option<Vec<Value>>),
+        UnmountItem: FnMut(Item, NodeKey<Item>),
+        FinishItem: FnMut(&Item, NodeKey<Item>),
+    {
+        if let Some(root) = self.root {
+            self.update_subtree(
+                root,
+                value,
+                mount_item,
+                update_item,
+                unmount_item,
+                finish_item,
+            )?;
+        } else {
+            self.root = Some(self.build_inner(None, value, mount_item, finish_item));
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> Option<NodeKey<Item>> {
+        self.root
+    }
+
+    /// `None` if `index` is stale.
+    pub fn get_children(&self, index: NodeKey<Item>) -> Option<&[NodeKey<Item>]> {
+        self.try_get_children(index).ok()
+    }
+
+    /// The parent of a node, or `None` for the root. Fails if `index` is
+    /// stale.
+    pub fn parent(&self, index: NodeKey<Item>) -> Result<Option<NodeKey<Item>>, ReactError> {
+        self.items.parent(index)
+    }
+
+    /// Iterates over the ancestors of a node, starting with its parent
+    /// and ending with the root. Fails if `index` is stale.
+    pub fn ancestors(&self, index: NodeKey<Item>) -> Result<Ancestors<'_, Item>, ReactError> {
+        Ok(Ancestors {
+            tree: self,
+            next: self.parent(index)?,
+        })
+    }
+
+    /// Returns the keys from `index` up to and including the root.
+    pub fn path_to_root(&self, index: NodeKey<Item>) -> Result<Vec<NodeKey<Item>>, ReactError> {
+        let mut path = vec![index];
+        for ancestor in self.ancestors(index)? {
+            path.push(ancestor?);
+        }
+        Ok(path)
+    }
+
+    pub fn contains(&self, index: NodeKey<Item>) -> bool {
+        self.items.contains(index)
+    }
+
+    /// `None` if `index` is stale. `try_get` also says why.
+    pub fn get(&self, index: NodeKey<Item>) -> Option<&Item> {
+        self.try_get(index).ok()
+    }
+
+    pub fn get_mut(&mut self, index: NodeKey<Item>) -> Option<&mut Item> {
+        self.try_get_mut(index).ok()
+    }
+
+    pub fn try_get(&self, index: NodeKey<Item>) -> Result<&Item, ReactError> {
+        self.items.check(index)?;
+        Ok(self.items.get(index))
+    }
+
+    pub fn try_get_mut(&mut self, index: NodeKey<Item>) -> Result<&mut Item, ReactError> {
+        self.items.check(index)?;
+        Ok(self.items.get_mut(index))
+    }
+
+    pub fn try_get_children(&self, index: NodeKey<Item>) -> Result<&[NodeKey<Item>], ReactError> {
+        self.items.check(index)?;
+        Ok(&self.items.get(index).get_children().children[..])
+    }
+}
+
+impl<Item> Default for FlatTree<Item>
+where
+    Item: GetNodeChildren,
+{
+    fn default() -> Self {
+        FlatTree::new()
+    }
+}
+
+/// The ancestors of a node, from its parent up to the root.
+///
+/// Parents are stored for as long as their children are, so a lookup
+/// only fails if the tree was left half unbuilt, for example by a panic
+/// while unmounting. The error is yielded in place of the missing
+/// ancestor, and the iterator stops after it.
+pub struct Ancestors<'a, Item: 'a> {
+    tree: &'a FlatTree<Item>,
+    next: Option<NodeKey<Item>>,
+}
+
+impl<'a, Item> Iterator for Ancestors<'a, Item>
+where
+    Item: GetNodeChildren,
+{
+    type Item = Result<NodeKey<Item>, ReactError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.next.take()?;
+        match self.tree.items.parent(key) {
+            Ok(parent) => {
+                self.next = parent;
+                Some(Ok(key))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arena, FlatTree, GetNodeChildren, NodeChildren, NodeKey};
+    use error::ReactError;
+
+    pub struct Node {
+        pub value: u32,
+        children: NodeChildren<Node>,
+    }
+
+    impl GetNodeChildren for Node {
+        fn get_children(&self) -> &NodeChildren<Self> {
+            &self.children
+        }
+
+        fn get_children_mut(&mut self) -> &mut NodeChildren<Self> {
+            &mut self.children
+        }
+    }
+
+    /// A node's value and children, to build trees from.
+    pub struct Spec(pub u32, pub Vec<Spec>);
+
+    pub fn node(value: u32) -> Node {
+        Node {
+            value,
+            children: NodeChildren::new(),
+        }
+    }
+
+    // 1
+    // +-2
+    // | +-3
+    // | +-4
+    // +-5
+    pub fn spec(offset: u32) -> Spec {
+        Spec(
+            1 + offset,
+            vec![
+                Spec(
+                    2 + offset,
+                    vec![Spec(3 + offset, vec![]), Spec(4 + offset, vec![])],
+                ),
+                Spec(5 + offset, vec![]),
+            ],
+        )
+    }
+
+    pub fn build(log: &mut Vec<u32>) -> FlatTree<Node> {
+        FlatTree::build(
+            spec(0),
+            |Spec(value, children), _| {
+                log.push(value);
+                (node(value), children)
+            },
+            |_, _| (),
+        )
+    }
+
+    pub fn post_order(tree: &FlatTree<Node>) -> Vec<u32> {
+        tree.recurse(|node, children: Vec<Vec<u32>>, _| {
+            let mut values = children.concat();
+            values.push(node.value);
+            values
+        })
+        .unwrap_or_default()
+    }
+
+    #[test]
+    fn build_visits_in_pre_order() {
+        let mut log = vec![];
+        build(&mut log);
+        assert_eq!(log, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn recurse_and_unbuild_visit_in_post_order() {
+        let tree = build(&mut vec![]);
+        assert_eq!(post_order(&tree), vec![3, 4, 2, 5, 1]);
+
+        let mut log = vec![];
+        tree.unbuild(|node, _: Vec<()>, _| log.push(node.value));
+        assert_eq!(log, vec![3, 4, 2, 5, 1]);
+    }
+
+    #[test]
+    fn update_visits_in_pre_order_and_keeps_keys() {
+        let mut tree = build(&mut vec![]);
+        let keys = tree.iter_keys();
+        let mut log = vec![];
+        tree.update_tree(
+            spec(10),
+            &mut |_, _| panic!("nothing should be mounted"),
+            &mut |mut node: Node, Spec(value, children), _| {
+                log.push((node.value, value));
+                node.value = value;
+                (node, Some(children))
+            },
+            &mut |_, _| panic!("nothing should be unmounted"),
+            &mut |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(log, vec![(1, 11), (2, 12), (3, 13), (4, 14), (5, 15)]);
+        assert_eq!(post_order(&tree), vec![13, 14, 12, 15, 11]);
+        assert_eq!(tree.iter_keys(), keys);
+    }
+
+    #[test]
+    fn update_without_children_keeps_the_subtree() {
+        let mut tree = build(&mut vec![]);
+        let mut log = vec![];
+        tree.update_tree(
+            Spec(1, vec![]),
+            &mut |_, _| panic!("nothing should be mounted"),
+            &mut |node: Node, _, _| {
+                log.push(node.value);
+                (node, None)
+            },
+            &mut |_, _| panic!("nothing should be unmounted"),
+            &mut |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(log, vec![1]);
+        assert_eq!(post_order(&tree), vec![3, 4, 2, 5, 1]);
+    }
+
+    #[test]
+    fn update_mounts_and_unmounts_trailing_children() {
+        let mut tree = build(&mut vec![]);
+        let mut log = vec![];
+        {
+            let log = ::std::cell::RefCell::new(&mut log);
+            tree.update_tree(
+                Spec(
+                    1,
+                    vec![
+                        Spec(2, vec![Spec(3, vec![])]),
+                        Spec(5, vec![]),
+                        Spec(6, vec![]),
+                    ],
+                ),
+                &mut |Spec(value, children), _| {
+                    log.borrow_mut().push(format!("mount {}", value));
+                    (node(value), children)
+                },
+                &mut |node: Node, Spec(_, children), _| (node, Some(children)),
+                &mut |node, _| log.borrow_mut().push(format!("unmount {}", node.value)),
+                &mut |_, _| (),
+            )
+            .unwrap();
+        }
+        assert_eq!(log, vec!["unmount 4", "mount 6"]);
+        assert_eq!(post_order(&tree), vec![3, 2, 5, 6, 1]);
+
+        let mut log = vec![];
+        tree.update_tree(
+            Spec(1, vec![]),
+            &mut |_, _| panic!("nothing should be mounted"),
+            &mut |node: Node, Spec(_, children), _| (node, Some(children)),
+            &mut |node, _| log.push(node.value),
+            &mut |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(log, vec![3, 2, 5, 6]);
+        assert_eq!(post_order(&tree), vec![1]);
+    }
+
+    #[test]
+    fn update_builds_a_tree_without_a_root() {
+        let mut tree = FlatTree::new();
+        let mut log = vec![];
+        tree.update_tree(
+            spec(0),
+            &mut |Spec(value, children), _| {
+                log.push(value);
+                (node(value), children)
+            },
+            &mut |_, _, _| panic!("nothing should be updated"),
+            &mut |_, _| panic!("nothing should be unmounted"),
+            &mut |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(log, vec![1, 2, 3, 4, 5]);
+        let root = tree.root().unwrap();
+        assert_eq!(tree.get(root).unwrap().value, 1);
+        assert_eq!(post_order(&tree), vec![3, 4, 2, 5, 1]);
+    }
+
+    #[test]
+    fn finish_visits_in_post_order() {
+        let mut finished = vec![];
+        let mut tree = FlatTree::build(
+            spec(0),
+            |Spec(value, children), _| (node(value), children),
+            |node: &Node, _| finished.push(node.value),
+        );
+        assert_eq!(finished, vec![3, 4, 2, 5, 1]);
+
+        // 2 keeps its children without updating them, so they aren't
+        // finished again.
+        let mut finished = vec![];
+        tree.update_tree(
+            Spec(1, vec![Spec(2, vec![]), Spec(5, vec![Spec(6, vec![])])]),
+            &mut |Spec(value, children), _| (node(value), children),
+            &mut |node: Node, Spec(value, children), _| {
+                let children = if value == 2 { None } else { Some(children) };
+                (node, children)
+            },
+            &mut |_, _| panic!("nothing should be unmounted"),
+            &mut |node: &Node, _| finished.push(node.value),
+        )
+        .unwrap();
+        assert_eq!(finished, vec![2, 6, 5, 1]);
+    }
+
+    #[test]
+    fn deep_trees_dont_overflow_the_stack() {
+        const DEPTH: u32 = 200_000;
+        let mut chain = |depth: u32, _| {
+            let children = if depth > 0 { vec![depth - 1] } else { vec![] };
+            (node(depth), children)
+        };
+        let mut tree = FlatTree::build(DEPTH, chain, |_, _| ());
+        assert_eq!(
+            tree.recurse(
+                |_, children: Vec<u32>, _| children.len() as u32 + children.iter().sum::<u32>()
+            ),
+            Some(DEPTH)
+        );
+
+        let mut updated = 0;
+        tree.update_tree(
+            DEPTH,
+            &mut chain,
+            &mut |node: Node, depth, _| {
+                updated += 1;
+                let children = if depth > 0 { vec![depth - 1] } else { vec![] };
+                (node, Some(children))
+            },
+            &mut |_, _| (),
+            &mut |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(updated, DEPTH + 1);
+
+        let mut unmounted = 0;
+        tree.unbuild(|_, _: Vec<()>, _| unmounted += 1);
+        assert_eq!(unmounted, DEPTH + 1);
+    }
+
+    #[test]
+    fn removed_slots_are_reused_with_a_new_generation() {
+        let mut arena = Arena::new();
+        let first = arena.reserve(None);
+        arena.insert(first, node(1));
+        let second = arena.reserve(Some(first));
+        arena.insert(second, node(2));
+
+        assert_eq!(arena.remove(first).value, 1);
+        let third = arena.reserve(None);
+        arena.insert(third, node(3));
+        assert_eq!(third.index(), first.index());
+        assert_eq!(third.generation(), first.generation() + 1);
+
+        assert!(!arena.contains(first));
+        assert!(arena.contains(third));
+        assert_eq!(arena.get(third).value, 3);
+        assert_eq!(arena.parent(second).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn stale_keys_are_detected() {
+        let mut arena = Arena::new();
+        let key = arena.reserve(None);
+        match arena.check(key) {
+            Err(ReactError::Invariant { .. }) => (),
+            _ => panic!("a reserved key should be reported as being built"),
+        }
+
+        arena.insert(key, node(1));
+        assert!(arena.check(key).is_ok());
+
+        arena.remove(key);
+        let reused = arena.reserve(None);
+        arena.insert(reused, node(2));
+        match arena.check(key) {
+            Err(ReactError::StaleKey {
+                index, generation, ..
+            }) => assert_eq!((index, generation), (key.index(), key.generation())),
+            _ => panic!("a removed key should be stale"),
+        }
+        assert!(arena.check(NodeKey::from_parts(7, 0)).is_err());
+    }
+
+    #[test]
+    fn unmounted_keys_are_stale_in_the_tree() {
+        let mut tree = build(&mut vec![]);
+        let keys = tree.iter_keys();
+        tree.update_tree(
+            Spec(1, vec![Spec(6, vec![])]),
+            &mut |Spec(value, children), _| (node(value), children),
+            &mut |node: Node, Spec(_, children), _| (node, Some(children)),
+            &mut |_, _| (),
+            &mut |_, _| (),
+        )
+        .unwrap();
+
+        // 2 is updated in place, and 3, 4 and 5 are unmounted.
+        for &key in &[keys[0], keys[1], keys[3]] {
+            assert!(!tree.contains(key));
+            assert!(tree.get(key).is_none());
+            assert!(tree.try_get(key).is_err());
+            assert!(tree.get_children(key).is_none());
+            assert!(tree.parent(key).is_err());
+            assert!(tree.ancestors(key).is_err());
+            assert!(tree.path_to_root(key).is_err());
+        }
+        assert_eq!(tree.path_to_root(keys[2]).unwrap(), vec![keys[2], keys[4]]);
+        assert_eq!(tree.try_get(keys[2]).unwrap().value, 2);
+    }
+
+    #[test]
+    fn missing_ancestors_are_errors() {
+        let mut tree = build(&mut vec![]);
+        let keys = tree.iter_keys();
+        let ancestors = tree.ancestors(keys[0]).unwrap();
+        assert_eq!(
+            ancestors.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![keys[2], keys[4]]
+        );
+
+        // What a panic halfway through unbuilding 2 would leave behind.
+        tree.items.remove(keys[2]);
+        let ancestors = tree.ancestors(keys[0]).unwrap().collect::<Vec<_>>();
+        match ancestors[..] {
+            [Err(ReactError::StaleKey { index, .. })] => assert_eq!(index, keys[2].index()),
+            _ => panic!("expected a stale parent, got {:?}", ancestors),
+        }
+        assert!(tree.path_to_root(keys[0]).is_err());
+    }
+
+    impl FlatTree<Node> {
+        /// Every key, in post-order.
+        fn iter_keys(&self) -> Vec<NodeKey<Node>> {
+            self.recurse(|_, children: Vec<Vec<NodeKey<Node>>>, key| {
+                let mut keys = children.concat();
+                keys.push(key);
+                keys
+            })
+            .unwrap_or_default()
+        }
+    }
+}
@@ -1,8 +1,41 @@
+#[cfg(feature = "macros")]
+extern crate react_rs_macros;
+#[cfg(feature = "serde_json")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "tracing")]
+#[macro_use]
+extern crate tracing;
+
+/// Enters a `tracing` span until the end of the enclosing block. Does
+/// nothing unless the `tracing` feature is enabled.
+macro_rules! enter_span {
+    ($span:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = $span.entered();
+    };
+}
+
 mod component;
+#[cfg(feature = "devtools")]
+pub mod devtools;
 mod element;
+mod error;
 mod flat_tree;
 mod reconciler;
+pub mod snapshot;
+pub mod testing;
 
 pub use component::{Component, RenderContext};
-pub use element::{Element, HostElement, DomNode};
-pub use reconciler::VirtualTree;
+pub use element::{DomNode, Element, HostElement, Layer, TryDomNode};
+pub use error::ReactError;
+pub use flat_tree::{
+    Ancestors, FlatTree, GetNodeChildren, Iter, NodeChildren, NodeKey, TraversalOrder,
+};
+#[cfg(feature = "macros")]
+pub use react_rs_macros::{component, react, Builder, HostElement};
+pub use reconciler::{
+    install_panic_hook, ComponentHandle, ComponentStack, InspectedNode, NodeKind, NodeProfile,
+    PassKind, PassProfile, Profile, RenderReason, RootKey, Roots, StateUpdater, TraceEvent,
+    TreeObserver, VirtualNode, VirtualTree, DEFAULT_FLUSH_LIMIT,
+};
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::panic;
+
+/// A node the reconciler is working inside of, on the current thread.
+struct Frame {
+    /// Set for stateful nodes.
+    name: Option<&'static str>,
+    /// Children that will still be visited before the reconciler leaves
+    /// this node's subtree.
+    remaining: usize,
+}
+
+thread_local! {
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The components the reconciler was inside of on this thread, outermost
+/// first. Displays as `App > Sidebar > Counter`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ComponentStack {
+    components: Vec<&'static str>,
+}
+
+impl ComponentStack {
+    /// The stack of the component currently being created, rendered or
+    /// committed on this thread. Empty outside of the reconciler.
+    pub fn current() -> ComponentStack {
+        let components = FRAMES
+            .try_with(|frames| match frames.try_borrow() {
+                Ok(frames) => frames.iter().filter_map(|frame| frame.name).collect(),
+                Err(_) => vec![],
+            })
+            .unwrap_or_default();
+        ComponentStack { components }
+    }
+
+    pub fn components(&self) -> &[&'static str] {
+        &self.components
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+impl fmt::Display for ComponentStack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, component) in self.components.iter().enumerate() {
+            if i > 0 {
+                write!(f, " > ")?;
+            }
+            write!(f, "{}", component)?;
+        }
+        Ok(())
+    }
+}
+
+/// The stack of a node that isn't being worked on, from the type
+/// names of the components above it.
+pub fn from_path(components: Vec<&'static str>) -> ComponentStack {
+    ComponentStack { components }
+}
+
+/// Installs a panic hook that prints the component stack after the
+/// previous hook's output, for panics raised inside the reconciler.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        let stack = ComponentStack::current();
+        if !stack.is_empty() {
+            eprintln!("component stack: {}", stack);
+        }
+    }));
+}
+
+/// Restores the stack that was current before `scope` when dropped,
+/// including while unwinding.
+pub struct Scope {
+    previous: Vec<Frame>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let previous = mem::take(&mut self.previous);
+        let _ = FRAMES.try_with(|frames| *frames.borrow_mut() = previous);
+    }
+}
+
+fn replace(frames: Vec<Frame>) -> Scope {
+    let previous = FRAMES.with(|current| mem::replace(&mut *current.borrow_mut(), frames));
+    Scope { previous }
+}
+
+/// Starts tracking a traversal whose first node sits below the
+/// components in `path`.
+pub fn scope(path: Vec<&'static str>) -> Scope {
+    let mut frames = path
+        .into_iter()
+        .map(|name| Frame {
+            name: Some(name),
+            remaining: 0,
+        })
+        .collect::<Vec<_>>();
+    frames.push(Frame {
+        name: None,
+        remaining: 1,
+    });
+    replace(frames)
+}
+
+/// Starts tracking a walk that moves between nodes with `truncate` and
+/// `enter`, rather than visiting every node of a traversal in order.
+pub fn walk() -> Scope {
+    replace(vec![])
+}
+
+/// Called as the traversal reaches a node, before it is mounted or
+/// updated. Traversals are depth first, so any subtree that has no
+/// children left to visit has been left.
+pub fn visit() {
+    FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        while frames.last().map(|frame| frame.remaining) == Some(0) {
+            frames.pop();
+        }
+        if let Some(parent) = frames.last_mut() {
+            parent.remaining -= 1;
+        }
+        frames.push(Frame {
+            name: None,
+            remaining: 0,
+        });
+    });
+}
+
+/// Names the node that was just visited.
+pub fn name(name: &'static str) {
+    FRAMES.with(|frames| {
+        if let Some(frame) = frames.borrow_mut().last_mut() {
+            frame.name = Some(name);
+        }
+    });
+}
+
+/// Records how many children the traversal will visit below the node
+/// that was just visited.
+pub fn children(count: usize) {
+    FRAMES.with(|frames| {
+        if let Some(frame) = frames.borrow_mut().last_mut() {
+            frame.remaining = count;
+        }
+    });
+}
+
+/// Leaves every node below the outermost `depth` on the stack.
+pub fn truncate(depth: usize) {
+    FRAMES.with(|frames| frames.borrow_mut().truncate(depth));
+}
+
+/// Enters a node below the innermost one on the stack. `name` is set
+/// for stateful nodes.
+pub fn enter(name: Option<&'static str>) {
+    FRAMES.with(|frames| {
+        frames.borrow_mut().push(Frame { name, remaining: 0 });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_path, ComponentStack};
+    use component::{Component, RenderContext};
+    use element::Element;
+    use reconciler::tests::{log, spec, take_log, Spec, Widget};
+    use reconciler::VirtualTree;
+
+    /// Renders its children like a `Logger`, and logs how many
+    /// components deep the component stack is whenever it runs.
+    struct Probe(&'static str);
+
+    impl Probe {
+        fn log(&self, method: &str) {
+            let depth = ComponentStack::current().components().len();
+            log(format!("{} {} at {}", method, self.0, depth));
+        }
+    }
+
+    impl Component<Widget> for Probe {
+        type Props = Spec;
+        type State = u32;
+
+        fn create(props: &Spec) -> (Self, u32) {
+            (Probe(props.name), 0)
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            self.log("render");
+            let children = ctx.props.children.iter().cloned();
+            Element::new_fragment(children.map(Element::new_stateful::<Probe>).collect())
+        }
+
+        fn did_mount(&mut self) {
+            self.log("did_mount");
+        }
+
+        fn did_update(&mut self) {
+            self.log("did_update");
+        }
+    }
+
+    // a
+    // +-b
+    // | +-c
+    // +-d
+    fn probes(revision: u32) -> Element<Widget> {
+        Element::new_stateful::<Probe>(spec(
+            "a",
+            revision,
+            vec![
+                spec("b", revision, vec![spec("c", revision, vec![])]),
+                spec("d", revision, vec![]),
+            ],
+        ))
+    }
+
+    #[test]
+    fn follows_the_reconciler() {
+        let mut tree = VirtualTree::mount(probes(0));
+        assert_eq!(
+            take_log(),
+            vec![
+                "render a at 1",
+                "render b at 2",
+                "render c at 3",
+                "render d at 2",
+                "did_mount c at 3",
+                "did_mount b at 2",
+                "did_mount d at 2",
+                "did_mount a at 1",
+            ]
+        );
+        assert!(ComponentStack::current().is_empty());
+
+        tree.update(probes(1)).unwrap();
+        assert_eq!(
+            take_log(),
+            vec![
+                "render a at 1",
+                "render b at 2",
+                "render c at 3",
+                "render d at 2",
+                "did_update c at 3",
+                "did_update b at 2",
+                "did_update d at 2",
+                "did_update a at 1",
+            ]
+        );
+
+        tree.find_components::<Probe>()[1]
+            .updater()
+            .set_state(|state| state + 1);
+        tree.flush().unwrap();
+        assert_eq!(take_log(), vec!["render b at 2", "did_update b at 2"]);
+        assert!(ComponentStack::current().is_empty());
+    }
+
+    #[test]
+    fn displays_outermost_first() {
+        let stack = from_path(vec!["App", "Sidebar", "Counter"]);
+        assert_eq!(stack.components(), ["App", "Sidebar", "Counter"]);
+        assert_eq!(stack.to_string(), "App > Sidebar > Counter");
+        assert!(!stack.is_empty());
+        assert!(ComponentStack::default().is_empty());
+        assert_eq!(ComponentStack::default().to_string(), "");
+    }
+}
@@ -1,5 +1,5 @@
-use element::DomNode;
 use element::HostElement;
+use element::{DomNode, TryDomNode};
 use flat_tree::NodeChildren;
 use reconciler::virtual_node::VirtualNode;
 
@@ -28,4 +28,11 @@ where
     {
         Some(Dom::new_dom_node(&self.element, children))
     }
+
+    pub fn try_render<'a, Dom>(&'a self, children: Vec<Dom>) -> Result<Dom, Dom::Error>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+    {
+        Dom::try_new_dom_node(&self.element, children)
+    }
 }
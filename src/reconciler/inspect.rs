@@ -0,0 +1,395 @@
+use component::Component;
+use element::{HostElement, Layer};
+use error::ReactError;
+use flat_tree::{NodeKey, TraversalOrder};
+use reconciler::{GenericStateUpdater, StateUpdater, StatefulNode, UpdateQueue};
+use reconciler::{VirtualNode, VirtualTree};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Host,
+    Stateful,
+    Fragment,
+    Layer,
+}
+
+/// Why a stateful node last rendered. A parent re-rendering with props
+/// that compare equal doesn't re-render the component, so that isn't a
+/// reason of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderReason {
+    /// The component was just created.
+    Mount,
+    /// The component's state was updated through its `StateUpdater`.
+    StateUpdate,
+    /// The parent re-rendered with props that compare unequal.
+    PropsChanged,
+    /// The parent re-rendered with props that compare unequal but whose
+    /// `Component::debug_props` output didn't change, which usually
+    /// means a field like a callback never compares equal. Only
+    /// detected in debug builds, and only for components that implement
+    /// `debug_props`; otherwise these renders count as `PropsChanged`.
+    UnstableProps,
+}
+
+/// Read-only snapshot of one mounted node, as returned by
+/// `VirtualTree::inspect`.
+pub struct InspectedNode<'a, H: 'a + HostElement> {
+    pub key: NodeKey<VirtualNode<H>>,
+    pub parent: Option<NodeKey<VirtualNode<H>>>,
+    pub depth: usize,
+    pub kind: NodeKind,
+    /// The component's type name, for stateful nodes.
+    pub type_name: Option<&'static str>,
+    pub children: &'a [NodeKey<VirtualNode<H>>],
+    /// The host element, for host nodes.
+    pub element: Option<&'a H>,
+    /// The layer, for layer nodes.
+    pub layer: Option<Layer>,
+    /// See `Component::debug_props`.
+    pub props: Option<String>,
+    /// See `Component::debug_state`.
+    pub state: Option<String>,
+    /// Why the node last rendered, for stateful nodes.
+    pub render_reason: Option<RenderReason>,
+    /// How many times the node has rendered since it was mounted, for
+    /// stateful nodes.
+    pub render_count: Option<u32>,
+}
+
+impl<'a, H> InspectedNode<'a, H>
+where
+    H: HostElement,
+{
+    fn new(
+        tree: &'a VirtualTree<H>,
+        key: NodeKey<VirtualNode<H>>,
+        depth: usize,
+    ) -> Result<InspectedNode<'a, H>, ReactError> {
+        let node = tree.tree.try_get(key)?;
+        let (kind, type_name, element, props, state) = match *node {
+            VirtualNode::Host(ref node) => (NodeKind::Host, None, Some(&node.element), None, None),
+            VirtualNode::Stateful(ref node) => (
+                NodeKind::Stateful,
+                Some(node.type_name()),
+                None,
+                node.debug_props(),
+                node.debug_state(),
+            ),
+            VirtualNode::Fragment(_) => (NodeKind::Fragment, None, None, None, None),
+            VirtualNode::Layer(..) => (NodeKind::Layer, None, None, None, None),
+        };
+        let (render_reason, render_count) = match *node {
+            VirtualNode::Stateful(ref node) => {
+                (Some(node.render_reason()), Some(node.render_count()))
+            }
+            _ => (None, None),
+        };
+        let layer = match *node {
+            VirtualNode::Layer(layer, _) => Some(layer),
+            _ => None,
+        };
+
+        Ok(InspectedNode {
+            key,
+            parent: tree.tree.parent(key)?,
+            depth,
+            kind,
+            type_name,
+            children: tree.tree.try_get_children(key)?,
+            element,
+            layer,
+            props,
+            state,
+            render_reason,
+            render_count,
+        })
+    }
+}
+
+impl<'a, H> fmt::Debug for InspectedNode<'a, H>
+where
+    H: HostElement + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InspectedNode")
+            .field("key", &self.key)
+            .field("parent", &self.parent)
+            .field("depth", &self.depth)
+            .field("kind", &self.kind)
+            .field("type_name", &self.type_name)
+            .field("children", &self.children)
+            .field("element", &self.element)
+            .field("layer", &self.layer)
+            .field("props", &self.props)
+            .field("state", &self.state)
+            .field("render_reason", &self.render_reason)
+            .field("render_count", &self.render_count)
+            .finish()
+    }
+}
+
+/// A mounted component found by `VirtualTree::find_components`.
+pub struct ComponentHandle<'a, H, Class>
+where
+    H: 'a + HostElement,
+    Class: 'a + Component<H>,
+{
+    key: NodeKey<VirtualNode<H>>,
+    node: &'a StatefulNode<H, Class>,
+    queue: &'a UpdateQueue<H>,
+}
+
+impl<'a, H, Class> ComponentHandle<'a, H, Class>
+where
+    H: HostElement,
+    Class: Component<H> + 'static,
+{
+    pub fn key(&self) -> NodeKey<VirtualNode<H>> {
+        self.key
+    }
+
+    pub fn component(&self) -> &'a Class {
+        self.node.component()
+    }
+
+    pub fn props(&self) -> &'a Class::Props {
+        self.node.props()
+    }
+
+    pub fn state(&self) -> &'a Class::State {
+        self.node.state()
+    }
+
+    /// An updater for this component, the same as the one passed to
+    /// its `render`. Updates are applied on the next `flush`.
+    pub fn updater(&self) -> StateUpdater<H, Class> {
+        GenericStateUpdater::new(self.queue, self.key).specialize()
+    }
+}
+
+impl<H> VirtualTree<H>
+where
+    H: HostElement,
+{
+    /// Lists every mounted node in pre-order.
+    pub fn inspect(&self) -> Vec<InspectedNode<'_, H>> {
+        // Keys yielded by `iter` are always mounted, so nothing is
+        // filtered out.
+        self.tree
+            .iter(TraversalOrder::PreOrder)
+            .filter_map(|(key, _, depth)| InspectedNode::new(self, key, depth).ok())
+            .collect()
+    }
+
+    /// Describes a single node. `depth` is measured from the root. Fails
+    /// if `key` is stale.
+    pub fn inspect_node(
+        &self,
+        key: NodeKey<VirtualNode<H>>,
+    ) -> Result<InspectedNode<'_, H>, ReactError> {
+        let depth = self.tree.path_to_root(key)?.len() - 1;
+        InspectedNode::new(self, key, depth)
+    }
+
+    /// Every mounted instance of `Class`, in pre-order.
+    pub fn find_components<Class>(&self) -> Vec<ComponentHandle<'_, H, Class>>
+    where
+        Class: Component<H> + 'static,
+    {
+        self.tree
+            .iter(TraversalOrder::PreOrder)
+            .filter_map(|(key, node, _)| {
+                match *node {
+                    VirtualNode::Stateful(ref node) => node.as_any().downcast_ref(),
+                    _ => None,
+                }
+                .map(|node| ComponentHandle {
+                    key,
+                    node,
+                    queue: &self.update_queue,
+                })
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, key: NodeKey<VirtualNode<H>>) -> bool {
+        self.tree.contains(key)
+    }
+
+    pub fn root(&self) -> Option<NodeKey<VirtualNode<H>>> {
+        self.tree.root()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeKind, RenderReason};
+    use component::{Component, RenderContext};
+    use element::{Element, Layer};
+    use reconciler::tests::{host, Counter, Widget};
+    use reconciler::VirtualTree;
+    use std::any::type_name;
+
+    /// Renders a host node named after its props, holding a fragment
+    /// with one item and an empty layer.
+    struct Panel;
+
+    impl Component<Widget> for Panel {
+        type Props = &'static str;
+        type State = u32;
+
+        fn create(_props: &&'static str) -> (Self, u32) {
+            (Panel, 0)
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            host(
+                ctx.props,
+                vec![
+                    Element::new_fragment(vec![host("item", vec![])]),
+                    Element::new_layer(Layer::new("popup", 5), vec![]),
+                ],
+            )
+        }
+
+        fn debug_props(props: &&'static str) -> Option<String> {
+            Some(format!("{:?}", props))
+        }
+
+        fn debug_state(state: &u32) -> Option<String> {
+            Some(state.to_string())
+        }
+    }
+
+    fn panel(name: &'static str) -> Element<Widget> {
+        Element::new_fragment(vec![Element::new_stateful::<Panel>(name)])
+    }
+
+    #[test]
+    fn describes_every_node() {
+        let mut tree = VirtualTree::mount(panel("panel"));
+        let nodes = tree.inspect();
+        let keys = nodes.iter().map(|node| node.key).collect::<Vec<_>>();
+        let position = |key| keys.iter().position(|&other| other == key).unwrap();
+        let shape = nodes
+            .iter()
+            .map(|node| {
+                let children = node.children.iter().map(|&key| position(key));
+                (
+                    node.kind,
+                    node.depth,
+                    node.parent.map(position),
+                    children.collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shape,
+            vec![
+                (NodeKind::Fragment, 0, None, vec![1]),
+                (NodeKind::Stateful, 1, Some(0), vec![2]),
+                (NodeKind::Host, 2, Some(1), vec![3, 5]),
+                (NodeKind::Fragment, 3, Some(2), vec![4]),
+                (NodeKind::Host, 4, Some(3), vec![]),
+                (NodeKind::Layer, 3, Some(2), vec![]),
+            ]
+        );
+        assert_eq!(tree.root(), Some(keys[0]));
+
+        let component = &nodes[1];
+        assert_eq!(component.type_name, Some(type_name::<Panel>()));
+        assert_eq!(component.props.as_ref().unwrap(), "\"panel\"");
+        assert_eq!(component.state.as_ref().unwrap(), "0");
+        assert_eq!(component.render_reason, Some(RenderReason::Mount));
+        assert_eq!(component.render_count, Some(1));
+        assert!(component.element.is_none());
+
+        let host = &nodes[2];
+        assert_eq!(host.element, Some(&Widget("panel")));
+        assert_eq!(host.type_name, None);
+        assert_eq!(host.props, None);
+        assert_eq!(host.render_count, None);
+        assert_eq!(nodes[5].layer, Some(Layer::new("popup", 5)));
+        assert_eq!(nodes[4].layer, None);
+
+        let panel_key = keys[1];
+        let item_key = keys[4];
+        tree.update(panel("renamed")).unwrap();
+        let node = tree.inspect_node(panel_key).unwrap();
+        assert_eq!((node.depth, node.parent), (1, Some(keys[0])));
+        assert_eq!(node.props.as_ref().unwrap(), "\"renamed\"");
+        assert_eq!(node.render_count, Some(2));
+        assert_eq!(tree.inspect_node(item_key).unwrap().depth, 4);
+
+        tree.update(Element::new_fragment(vec![])).unwrap();
+        assert!(!tree.contains(panel_key));
+        assert!(tree.inspect_node(panel_key).is_err());
+        assert_eq!(tree.inspect().len(), 1);
+    }
+
+    /// Props that never compare equal, like props holding a closure.
+    #[derive(Clone, Debug)]
+    struct Unstable(&'static str);
+
+    impl PartialEq for Unstable {
+        fn eq(&self, _other: &Unstable) -> bool {
+            false
+        }
+    }
+
+    struct Label;
+
+    impl Component<Widget> for Label {
+        type Props = Unstable;
+        type State = ();
+
+        fn create(_props: &Unstable) -> (Self, ()) {
+            (Label, ())
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            host(ctx.props.0, vec![])
+        }
+
+        fn debug_props(props: &Unstable) -> Option<String> {
+            Some(format!("{:?}", props))
+        }
+    }
+
+    /// The render reason of the first stateful node.
+    fn reason(tree: &VirtualTree<Widget>) -> RenderReason {
+        let nodes = tree.inspect();
+        let mut reasons = nodes.iter().filter_map(|node| node.render_reason);
+        reasons.next().unwrap()
+    }
+
+    #[test]
+    fn reports_why_components_rendered() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Counter>(1));
+        assert_eq!(reason(&tree), RenderReason::Mount);
+        tree.update(Element::new_stateful::<Counter>(2)).unwrap();
+        assert_eq!(reason(&tree), RenderReason::PropsChanged);
+        tree.flush().unwrap();
+        assert_eq!(reason(&tree), RenderReason::StateUpdate);
+    }
+
+    #[test]
+    fn reports_props_that_only_compare_unequal() {
+        let label = |text| Element::new_stateful::<Label>(Unstable(text));
+        let mut tree = VirtualTree::mount(label("a"));
+        assert_eq!(reason(&tree), RenderReason::Mount);
+
+        tree.update(label("a")).unwrap();
+        let expected = if cfg!(debug_assertions) {
+            RenderReason::UnstableProps
+        } else {
+            RenderReason::PropsChanged
+        };
+        assert_eq!(reason(&tree), expected);
+
+        tree.update(label("b")).unwrap();
+        assert_eq!(reason(&tree), RenderReason::PropsChanged);
+    }
+}
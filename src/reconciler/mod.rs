@@ -1,21 +1,37 @@
 use component::Component;
-use element::DomNode;
-use element::{Element, HostElement};
+use element::{DomNode, TryDomNode};
+use element::{Element, HostElement, Layer};
+use error::ReactError;
 use flat_tree::FlatTree;
 use flat_tree::GetNodeChildren;
 use flat_tree::NodeChildren;
 use flat_tree::NodeKey;
-use reconciler::stateful_node::StatefulNode;
-use std::any::Any;
+use std::any::{type_name, Any};
+use std::collections::HashMap;
+use std::error::Error;
 use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
+mod component_stack;
 mod host_node;
+mod inspect;
+mod observer;
+mod profiler;
+mod roots;
 mod stateful_node;
 mod virtual_node;
 
+pub use self::component_stack::{install_panic_hook, ComponentStack};
 pub use self::host_node::HostNode;
-pub use self::stateful_node::StatefulNodeWrapper;
+pub use self::inspect::{ComponentHandle, InspectedNode, NodeKind, RenderReason};
+use self::observer::Observers;
+pub use self::observer::TreeObserver;
+use self::profiler::Profiler;
+pub use self::profiler::{NodeProfile, PassKind, PassProfile, Profile, TraceEvent};
+pub use self::roots::{RootKey, Roots};
+pub use self::stateful_node::{StatefulNode, StatefulNodeWrapper};
 pub use self::virtual_node::VirtualNode;
 
 pub trait StatefulElementWrapper<H: HostElement>: Any {
@@ -24,8 +40,28 @@ pub trait StatefulElementWrapper<H: HostElement>: Any {
     fn as_any(&self) -> &dyn Any;
 }
 
+/// Number of passes `VirtualTree::flush` makes before it decides that
+/// components are scheduling updates in a loop.
+pub const DEFAULT_FLUSH_LIMIT: usize = 50;
+
+/// Source of the ids that tell apart trees sharing an update queue.
+static NEXT_TREE: AtomicUsize = AtomicUsize::new(0);
+
+/// A queued state update, applied to the tree it was scheduled for.
+type UpdateFn<H> = Box<dyn FnMut(&mut VirtualTree<H>) -> Result<(), ReactError>>;
+
+struct QueuedUpdate<H: HostElement> {
+    /// The tree whose node scheduled the update.
+    tree: usize,
+    component: &'static str,
+    func: UpdateFn<H>,
+}
+
 struct UpdateQueue<H: HostElement> {
-    queue: Arc<Mutex<Vec<Box<FnMut(&mut VirtualTree<H>)>>>>,
+    queue: Arc<Mutex<Vec<QueuedUpdate<H>>>>,
+    observers: Observers<H>,
+    /// The tree that updates pushed through this handle are for.
+    tree: usize,
 }
 
 impl<H> Clone for UpdateQueue<H>
@@ -35,6 +71,8 @@ where
     fn clone(&self) -> Self {
         UpdateQueue {
             queue: self.queue.clone(),
+            observers: self.observers.clone(),
+            tree: self.tree,
         }
     }
 }
@@ -104,23 +142,48 @@ where
         let index = self.node;
         let mut func = Some(func);
         let updater = self.unspecialize();
-        self.queue.push(move |tree| {
-            let element = match tree.tree.get_mut(index) {
-                VirtualNode::Host(_) => panic!(),
-                VirtualNode::Fragment(_) => panic!(),
-                VirtualNode::Stateful(node) => {
-                    match node.as_any_mut().downcast_mut::<StatefulNode<H, Class>>() {
-                        Some(ref mut node) => {
-                            node.update_state(func.take().unwrap(), updater.clone())
-                        }
-                        None => panic!(),
-                    }
-                }
+        self.queue
+            .observers
+            .notify(move |observer| observer.update_enqueued(index, type_name::<Class>()));
+        self.queue.push(type_name::<Class>(), move |tree| {
+            // The component was unmounted, or replaced by a different
+            // node in the same place, before the update was applied.
+            let is_class = match tree.tree.get(index) {
+                Some(VirtualNode::Stateful(node)) => node.as_any().is::<StatefulNode<H, Class>>(),
+                _ => false,
             };
-            let child = tree.tree.get_children(index).first().map(|&x| x);
-            if let Some(child) = child {
-                tree.update_subtree(child, element);
+            if !is_class {
+                return Ok(());
             }
+            tree.update_queue
+                .observers
+                .notify(move |observer| observer.update_applied(index, type_name::<Class>()));
+            let stack = component_stack::scope(tree.component_path(index));
+            let timer = tree.profiler.timer();
+            let element = match *tree.tree.try_get_mut(index)? {
+                VirtualNode::Stateful(ref mut node) => node
+                    .as_any_mut()
+                    .downcast_mut::<StatefulNode<H, Class>>()
+                    .unwrap()
+                    .update_state(func.take().unwrap(), updater.clone()),
+                _ => unreachable!(),
+            };
+            tree.profiler
+                .visit(index, timer, Some(type_name::<Class>()));
+            drop(stack);
+            tree.update_queue
+                .observers
+                .notify(move |observer| observer.updated(index, Some(type_name::<Class>())));
+            let child = tree.tree.try_get_children(index)?.first().copied();
+            let result = match child {
+                Some(child) => tree.reconcile(Some(child), element),
+                None => Ok(()),
+            };
+            // The component finishes and commits after its subtree, like
+            // it would have if the traversal had started above it.
+            tree.profiler.finish(index);
+            tree.pending_commits.push(index);
+            result
         })
     }
 }
@@ -132,17 +195,63 @@ where
     fn new() -> UpdateQueue<H> {
         UpdateQueue {
             queue: Arc::new(Mutex::new(vec![])),
+            observers: Observers::new(),
+            tree: NEXT_TREE.fetch_add(1, Ordering::Relaxed),
         }
     }
 
-    pub fn push<Func: FnMut(&mut VirtualTree<H>) + 'static>(&self, func: Func) {
-        self.queue.lock().unwrap().push(Box::new(func));
+    /// A handle on the same queue for another tree, with observers of
+    /// its own, since `NodeKey`s are only unique within one tree.
+    fn for_new_tree(&self) -> UpdateQueue<H> {
+        UpdateQueue {
+            queue: self.queue.clone(),
+            observers: Observers::new(),
+            tree: NEXT_TREE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn push<Func: FnMut(&mut VirtualTree<H>) -> Result<(), ReactError> + 'static>(
+        &self,
+        component: &'static str,
+        func: Func,
+    ) {
+        self.queue.lock().unwrap().push(QueuedUpdate {
+            tree: self.tree,
+            component,
+            func: Box::new(func),
+        });
+    }
+
+    fn drain(&self) -> Vec<QueuedUpdate<H>> {
+        self.queue.lock().unwrap().drain(..).collect()
+    }
+
+    /// Takes the updates for `tree`, leaving those of other trees queued.
+    fn drain_tree(&self, tree: usize) -> Vec<QueuedUpdate<H>> {
+        let mut queue = self.queue.lock().unwrap();
+        let (items, others) = queue.drain(..).partition(|item| item.tree == tree);
+        *queue = others;
+        items
+    }
+
+    /// Puts updates that weren't applied back in front of any queued
+    /// since.
+    fn requeue<I: IntoIterator<Item = QueuedUpdate<H>>>(&self, items: I) {
+        let mut queue = self.queue.lock().unwrap();
+        let queued = queue.drain(..).collect::<Vec<_>>();
+        queue.extend(items);
+        queue.extend(queued);
     }
 }
 
 pub struct VirtualTree<H: HostElement> {
     tree: FlatTree<VirtualNode<H>>,
     update_queue: UpdateQueue<H>,
+    /// Stateful nodes that owe a lifecycle method to the next commit.
+    pending_commits: Vec<NodeKey<VirtualNode<H>>>,
+    flush_limit: usize,
+    revision: u64,
+    profiler: Profiler<H>,
 }
 
 impl<H> GetNodeChildren for VirtualNode<H>
@@ -154,6 +263,7 @@ where
             VirtualNode::Host(ref host_node) => &host_node.children,
             VirtualNode::Stateful(ref stateful_node) => stateful_node.get_children(),
             VirtualNode::Fragment(ref children) => children,
+            VirtualNode::Layer(_, ref children) => children,
         }
     }
 
@@ -162,6 +272,7 @@ where
             VirtualNode::Host(ref mut host_node) => &mut host_node.children,
             VirtualNode::Stateful(ref mut stateful_node) => stateful_node.get_children_mut(),
             VirtualNode::Fragment(ref mut children) => children,
+            VirtualNode::Layer(_, ref mut children) => children,
         }
     }
 }
@@ -171,75 +282,1083 @@ where
     H: HostElement,
 {
     pub fn mount(element: Element<H>) -> Self {
-        let queue = UpdateQueue::new();
-        let tree = FlatTree::build(element, |node, index| {
-            VirtualNode::mount(node, GenericStateUpdater::new(&queue, index))
-        });
+        VirtualTree::mount_with_observers(element, vec![])
+    }
 
-        VirtualTree {
-            tree: tree,
-            update_queue: queue,
+    /// Like `mount`, but the observers also see the initial mount.
+    pub fn mount_with_observers(
+        element: Element<H>,
+        observers: Vec<Box<dyn TreeObserver<H>>>,
+    ) -> Self {
+        let queue = UpdateQueue::new();
+        for observer in observers {
+            queue.observers.add(observer);
         }
+        VirtualTree::mount_with_queue(element, queue)
     }
 
-    fn update_subtree(&mut self, node: NodeKey<VirtualNode<H>>, element: Element<H>) {
-        let queue = &self.update_queue;
-        self.tree.update_subtree(
-            node,
+    /// Mounts `element` as the tree that `queue` schedules updates for.
+    fn mount_with_queue(element: Element<H>, queue: UpdateQueue<H>) -> Self {
+        enter_span!(debug_span!("mount"));
+        let stack = component_stack::scope(vec![]);
+        let mut pending_commits = vec![];
+        let tree = FlatTree::build(
             element,
-            &mut |node, index| VirtualNode::mount(node, GenericStateUpdater::new(queue, index)),
-            &mut |node, element, index| {
-                VirtualNode::update(node, element, GenericStateUpdater::new(queue, index))
+            |node, index| {
+                component_stack::visit();
+                let (node, children) =
+                    VirtualNode::mount(node, GenericStateUpdater::new(&queue, index));
+                component_stack::children(children.len());
+                let type_name = node.type_name();
+                queue
+                    .observers
+                    .notify(move |observer| observer.mounted(index, type_name));
+                (node, children)
+            },
+            |node, index| {
+                if node.has_pending_commit() {
+                    pending_commits.push(index);
+                }
             },
-            &mut |node, index| VirtualNode::unmount(node, GenericStateUpdater::new(queue, index)),
         );
-    }
+        drop(stack);
 
-    pub fn flush(&mut self) {
-        let items = {
-            let mut guard = self.update_queue.queue.lock().unwrap();
-            let items = guard
-                .drain(..)
-                .collect::<Vec<Box<FnMut(&mut VirtualTree<H>)>>>();
-            items
+        let mut tree = VirtualTree {
+            tree: tree,
+            update_queue: queue,
+            pending_commits,
+            flush_limit: DEFAULT_FLUSH_LIMIT,
+            revision: 0,
+            profiler: Profiler::new(),
         };
-        for mut func in items.into_iter() {
-            (func)(self);
+        tree.commit();
+        tree
+    }
+
+    /// Runs the lifecycle methods owed by the last render phase. Nodes
+    /// are recorded as the traversal leaves them, in post-order, so a
+    /// component's `did_mount` and `did_update` only fire once its whole
+    /// subtree is in place.
+    fn commit(&mut self) {
+        // The nodes from the root down to the one being committed. Keys
+        // are committed in post-order, so moving from one to the next
+        // only enters the nodes that weren't on the path yet.
+        let mut path = vec![];
+        let mut depths = HashMap::new();
+        let _stack = component_stack::walk();
+        for key in mem::take(&mut self.pending_commits) {
+            // A node can be recorded by several updates in one flush, or
+            // be unmounted by a later one.
+            let pending = self
+                .tree
+                .get(key)
+                .is_some_and(VirtualNode::has_pending_commit);
+            if !pending {
+                continue;
+            }
+
+            let mut entered = vec![];
+            let mut next = Some(key);
+            let depth = loop {
+                match next {
+                    Some(ancestor) => match depths.get(&ancestor) {
+                        Some(&depth) => break depth + 1,
+                        None => {
+                            entered.push(ancestor);
+                            next = self.tree.parent(ancestor).unwrap_or_default();
+                        }
+                    },
+                    None => break 0,
+                }
+            };
+            for left in path.drain(depth..) {
+                depths.remove(&left);
+            }
+            component_stack::truncate(depth);
+            for node in entered.into_iter().rev() {
+                depths.insert(node, path.len());
+                path.push(node);
+                component_stack::enter(self.tree.get(node).and_then(VirtualNode::type_name));
+            }
+
+            let timer = self.profiler.timer();
+            let node = match self.tree.get_mut(key) {
+                Some(node) => node,
+                None => continue,
+            };
+            if let Some(method) = node.commit() {
+                self.profiler
+                    .lifecycle(key, node.type_name().unwrap(), method, timer);
+            }
         }
+        self.revision += 1;
     }
 
-    pub fn update(&mut self, element: Element<H>) {
-        self.flush();
+    /// The type names of the components from the root down to `key`,
+    /// or none if `key` is stale.
+    fn component_path(&self, key: NodeKey<VirtualNode<H>>) -> Vec<&'static str> {
+        let mut path = self
+            .tree
+            .path_to_root(key)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|key| self.tree.get(key).and_then(VirtualNode::type_name))
+            .collect::<Vec<_>>();
+        path.reverse();
+        path
+    }
 
+    /// Registers an observer for everything that happens to the tree
+    /// from now on.
+    pub fn add_observer<O>(&mut self, observer: O)
+    where
+        O: TreeObserver<H> + 'static,
+    {
+        self.update_queue.observers.add(Box::new(observer));
+    }
+
+    /// Counter that increases every time changes are committed to the
+    /// tree, so observers can tell whether it needs to be looked at again.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Reconciles `element` against the subtree at `node`, or against
+    /// the whole tree.
+    fn reconcile(
+        &mut self,
+        node: Option<NodeKey<VirtualNode<H>>>,
+        element: Element<H>,
+    ) -> Result<(), ReactError> {
+        let parent = match node {
+            Some(node) => self.tree.parent(node)?,
+            None => None,
+        };
+        let _stack = component_stack::scope(parent.map_or(vec![], |key| self.component_path(key)));
         let queue = &self.update_queue;
-        self.tree.update_tree(
-            element,
-            &mut |node, index| VirtualNode::mount(node, GenericStateUpdater::new(&queue, index)),
-            &mut |node, element, index| {
-                VirtualNode::update(node, element, GenericStateUpdater::new(&queue, index))
-            },
-            &mut |node, index| VirtualNode::unmount(node, GenericStateUpdater::new(&queue, index)),
-        );
+        let profiler = &self.profiler;
+        let pending_commits = &mut self.pending_commits;
+        let mut mount_item = |element, index| {
+            component_stack::visit();
+            mount_node(queue, profiler, element, index)
+        };
+        let mut update_item = |node, element, index| {
+            component_stack::visit();
+            let timer = profiler.timer();
+            match VirtualNode::update(node, element, GenericStateUpdater::new(queue, index)) {
+                Ok((node, children)) => {
+                    component_stack::children(children.as_ref().map_or(0, Vec::len));
+                    let rendered = children.as_ref().and_then(|_| node.type_name());
+                    profiler.visit(index, timer, rendered);
+                    if children.is_some() {
+                        let type_name = node.type_name();
+                        queue
+                            .observers
+                            .notify(move |observer| observer.updated(index, type_name));
+                    }
+                    (node, children)
+                }
+                // A node of a different kind takes the old one's place.
+                Err((node, element)) => {
+                    unmount_node(queue, profiler, node, index);
+                    let (node, children) = mount_node(queue, profiler, element, index);
+                    (node, Some(children))
+                }
+            }
+        };
+        let mut unmount_item = |node, index| unmount_node(queue, profiler, node, index);
+        let mut finish_item = |node: &VirtualNode<H>, index| {
+            profiler.finish(index);
+            if node.has_pending_commit() {
+                pending_commits.push(index);
+            }
+        };
+
+        match node {
+            Some(node) => self
+                .tree
+                .update_subtree(
+                    node,
+                    element,
+                    &mut mount_item,
+                    &mut update_item,
+                    &mut unmount_item,
+                    &mut finish_item,
+                )
+                .map(|_| ()),
+            None => self.tree.update_tree(
+                element,
+                &mut mount_item,
+                &mut update_item,
+                &mut unmount_item,
+                &mut finish_item,
+            ),
+        }
+    }
+
+    /// Sets how many passes `flush` may make before it gives up. Each
+    /// pass applies every update queued so far and runs the commit
+    /// phase, which may queue more.
+    pub fn set_flush_limit(&mut self, limit: usize) {
+        self.flush_limit = limit;
+    }
+
+    /// Applies queued state updates until the queue stays empty.
+    ///
+    /// Fails with `ReactError::FlushLimit` if updates are still being
+    /// scheduled after the flush limit is reached. Those updates, and the
+    /// ones after an update that fails, stay queued for the next flush.
+    pub fn flush(&mut self) -> Result<(), ReactError> {
+        enter_span!(debug_span!("flush"));
+        let queue = self.update_queue.clone();
+        let limit = self.flush_limit;
+        flush_trees(&queue, limit, &mut [self], false)
+    }
+
+    /// Flushes the queued state updates, then reconciles `element`
+    /// against the tree.
+    ///
+    /// `element` is reconciled even if the flush fails, so a component
+    /// that keeps scheduling updates can't stop the rest of the tree from
+    /// changing. The updates that weren't applied stay queued, and the
+    /// flush's error is returned once the tree is up to date.
+    pub fn update(&mut self, element: Element<H>) -> Result<(), ReactError> {
+        enter_span!(debug_span!("update"));
+        let flushed = self.flush();
+        self.update_root(element)?;
+        flushed
+    }
+
+    /// Reconciles `element` against the whole tree and commits it,
+    /// without flushing first.
+    fn update_root(&mut self, element: Element<H>) -> Result<(), ReactError> {
+        self.profiler.begin_pass();
+        self.reconcile(None, element)?;
+        self.commit();
+        self.profiler.end_pass(PassKind::Update);
+        Ok(())
+    }
+
+    /// Starts recording how long each stateful node takes to render,
+    /// run its lifecycle methods and reconcile its subtree, over every
+    /// later `update` and `flush`. Discards any profile already being
+    /// recorded.
+    pub fn start_profiling(&mut self) {
+        self.profiler.start();
+    }
+
+    /// Stops profiling and returns what was recorded since
+    /// `start_profiling`.
+    pub fn stop_profiling(&mut self) -> Option<Profile<H>> {
+        self.profiler.stop()
+    }
+
+    /// Returns what was recorded so far and keeps profiling from a
+    /// clean slate, for example once per frame.
+    pub fn take_profile(&mut self) -> Option<Profile<H>> {
+        let profile = self.profiler.stop();
+        if profile.is_some() {
+            self.profiler.start();
+        }
+        profile
     }
 
     pub fn unmount(self) {
+        enter_span!(debug_span!("unmount"));
         let queue = self.update_queue;
         self.tree.unbuild(|node, _, index| {
-            VirtualNode::unmount(node, GenericStateUpdater::new(&queue, index))
+            let type_name = node.type_name();
+            let stack = component_stack::scope(type_name.into_iter().collect());
+            VirtualNode::unmount(node, GenericStateUpdater::new(&queue, index));
+            drop(stack);
+            queue
+                .observers
+                .notify(move |observer| observer.unmounted(index, type_name));
         });
     }
 
-    pub fn render<'a, Dom>(&'a self) -> Option<Dom>
+    /// Builds the host's tree from the mounted host elements.
+    ///
+    /// Fails with `ReactError::MultipleRoots` if the root renders more
+    /// than one top-level host node; use `render_all` for those.
+    pub fn render<'a, Dom>(&'a self) -> Result<Option<Dom>, ReactError>
     where
         Dom: DomNode<'a, Widget = H>,
     {
-        let result = self
+        enter_span!(debug_span!("render"));
+        single_root(self.render_nodes())
+    }
+
+    /// Builds a host node for every top-level host element, in order,
+    /// for roots such as fragments that render several of them.
+    pub fn render_all<'a, Dom>(&'a self) -> Vec<Dom>
+    where
+        Dom: DomNode<'a, Widget = H>,
+    {
+        enter_span!(debug_span!("render"));
+        self.render_nodes()
+    }
+
+    /// Builds the host nodes of every layer, in the order the host should
+    /// draw them: ascending `z_index`, with `Layer::BASE` holding the
+    /// main tree and coming first among layers at z-index 0. Content
+    /// rendered into a layer is left out of its parent host node.
+    pub fn render_layers<'a, Dom>(&'a self) -> Vec<(Layer, Vec<Dom>)>
+    where
+        Dom: DomNode<'a, Widget = H>,
+    {
+        enter_span!(debug_span!("render"));
+        let mut layers = vec![];
+        let base = self
             .tree
-            .recurse(|node, children| {
-                node.render(children.into_iter().flatten().collect::<Vec<Dom>>())
+            .recurse(|node, children, _| {
+                let children = children.into_iter().flatten().collect::<Vec<Dom>>();
+                match *node {
+                    VirtualNode::Layer(layer, _) => {
+                        add_to_layer(&mut layers, layer, children);
+                        vec![]
+                    }
+                    _ => node.render(children),
+                }
             })
             .unwrap_or(vec![]);
-        assert!(result.len() <= 1);
-        result.into_iter().next()
+        order_layers(base, layers)
+    }
+
+    fn render_nodes<'a, Dom>(&'a self) -> Vec<Dom>
+    where
+        Dom: DomNode<'a, Widget = H>,
+    {
+        self.tree
+            .recurse(|node, children, _| {
+                node.render(children.into_iter().flatten().collect::<Vec<Dom>>())
+            })
+            .unwrap_or(vec![])
+    }
+
+    /// Like `render`, but for hosts whose nodes can fail to be created.
+    /// Stops creating nodes at the first failure.
+    pub fn try_render<'a, Dom>(&'a self) -> Result<Option<Dom>, ReactError>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+        Dom::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        enter_span!(debug_span!("render"));
+        let mut errors = vec![];
+        match self.try_render_nodes(&mut errors, true, &mut vec![]) {
+            Some(nodes) => single_root(nodes),
+            None => Err(errors.remove(0)),
+        }
+    }
+
+    /// Like `render_layers`, but for hosts whose nodes can fail to be
+    /// created. Stops creating nodes at the first failure.
+    pub fn try_render_layers<'a, Dom>(&'a self) -> Result<Vec<(Layer, Vec<Dom>)>, ReactError>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+        Dom::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        enter_span!(debug_span!("render"));
+        let mut errors = vec![];
+        let mut layers = vec![];
+        match self.try_render_nodes(&mut errors, true, &mut layers) {
+            Some(base) => Ok(order_layers(base, layers)),
+            None => Err(errors.remove(0)),
+        }
+    }
+
+    /// Like `try_render`, but keeps creating every node that doesn't
+    /// depend on a failed one, and returns all of the failures.
+    pub fn try_render_collecting<'a, Dom>(&'a self) -> Result<Option<Dom>, Vec<ReactError>>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+        Dom::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        enter_span!(debug_span!("render"));
+        let mut errors = vec![];
+        match self.try_render_nodes(&mut errors, false, &mut vec![]) {
+            Some(nodes) => single_root(nodes).map_err(|err| vec![err]),
+            None => Err(errors),
+        }
+    }
+
+    /// Creates the host's nodes, or returns `None` after pushing the
+    /// reason to `errors`. A node whose children failed isn't created.
+    /// Content rendered into a layer goes to `layers`.
+    fn try_render_nodes<'a, Dom>(
+        &'a self,
+        errors: &mut Vec<ReactError>,
+        stop_at_first: bool,
+        layers: &mut Vec<(Layer, Vec<Dom>)>,
+    ) -> Option<Vec<Dom>>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+        Dom::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        self.tree
+            .recurse(|node, children: Vec<Option<Vec<Dom>>>, key| {
+                if stop_at_first && !errors.is_empty() {
+                    return None;
+                }
+                let children = children.into_iter().collect::<Option<Vec<_>>>()?;
+                let children = children.into_iter().flatten().collect();
+                if let VirtualNode::Layer(layer, _) = *node {
+                    add_to_layer(layers, layer, children);
+                    return Some(vec![]);
+                }
+                match node.try_render(children) {
+                    Ok(nodes) => Some(nodes),
+                    Err(err) => {
+                        errors.push(ReactError::Host {
+                            error: err.into(),
+                            component_stack: component_stack::from_path(self.component_path(key)),
+                        });
+                        None
+                    }
+                }
+            })
+            .unwrap_or(Some(vec![]))
+    }
+}
+
+/// Mounts `element` as the node at `index`, reporting it to the
+/// profiler and observers.
+fn mount_node<H>(
+    queue: &UpdateQueue<H>,
+    profiler: &Profiler<H>,
+    element: Element<H>,
+    index: NodeKey<VirtualNode<H>>,
+) -> (VirtualNode<H>, Vec<Element<H>>)
+where
+    H: HostElement,
+{
+    let timer = profiler.timer();
+    let (node, children) = VirtualNode::mount(element, GenericStateUpdater::new(queue, index));
+    component_stack::children(children.len());
+    let type_name = node.type_name();
+    profiler.visit(index, timer, type_name);
+    queue
+        .observers
+        .notify(move |observer| observer.mounted(index, type_name));
+    (node, children)
+}
+
+/// Unmounts the node that was at `index`, reporting it to the profiler
+/// and observers.
+fn unmount_node<H>(
+    queue: &UpdateQueue<H>,
+    profiler: &Profiler<H>,
+    node: VirtualNode<H>,
+    index: NodeKey<VirtualNode<H>>,
+) where
+    H: HostElement,
+{
+    let timer = profiler.timer();
+    let type_name = node.type_name();
+    // The ancestors of unmounted nodes can't be looked up while the tree
+    // is being updated.
+    let stack = component_stack::scope(type_name.into_iter().collect());
+    VirtualNode::unmount(node, GenericStateUpdater::new(queue, index));
+    drop(stack);
+    if let Some(type_name) = type_name {
+        profiler.lifecycle(index, type_name, "will_unmount", timer);
+    }
+    queue
+        .observers
+        .notify(move |observer| observer.unmounted(index, type_name));
+}
+
+/// Adds `nodes` to `layer`, keeping layers in the order they were first
+/// rendered into.
+fn add_to_layer<Dom>(layers: &mut Vec<(Layer, Vec<Dom>)>, layer: Layer, nodes: Vec<Dom>) {
+    match layers.iter_mut().find(|&&mut (other, _)| other == layer) {
+        Some(&mut (_, ref mut existing)) => existing.extend(nodes),
+        None => layers.push((layer, nodes)),
+    }
+}
+
+/// Puts the main tree's nodes first in `Layer::BASE` and sorts the
+/// layers by `z_index`.
+fn order_layers<Dom>(
+    mut base: Vec<Dom>,
+    mut layers: Vec<(Layer, Vec<Dom>)>,
+) -> Vec<(Layer, Vec<Dom>)> {
+    if let Some(index) = layers.iter().position(|&(layer, _)| layer == Layer::BASE) {
+        base.extend(layers.remove(index).1);
+    }
+    layers.insert(0, (Layer::BASE, base));
+    layers.sort_by_key(|&(layer, _)| layer.z_index);
+    layers
+}
+
+/// The only node of a render, failing if there are several.
+fn single_root<Dom>(nodes: Vec<Dom>) -> Result<Option<Dom>, ReactError> {
+    if nodes.len() > 1 {
+        return Err(ReactError::MultipleRoots { count: nodes.len() });
+    }
+    Ok(nodes.into_iter().next())
+}
+
+/// Applies queued updates to `trees` until the queue stays empty, handing
+/// each update to the tree it was scheduled for. With `all`, updates for
+/// trees that aren't in `trees` are dropped; otherwise they stay queued.
+fn flush_trees<H>(
+    queue: &UpdateQueue<H>,
+    limit: usize,
+    trees: &mut [&mut VirtualTree<H>],
+    all: bool,
+) -> Result<(), ReactError>
+where
+    H: HostElement,
+{
+    let mut passes = 0;
+    let mut flushed = vec![false; trees.len()];
+    let result = loop {
+        let items = if all {
+            queue.drain()
+        } else {
+            let mut items = vec![];
+            for tree in trees.iter() {
+                items.extend(queue.drain_tree(tree.update_queue.tree));
+            }
+            items
+        };
+        if items.is_empty() {
+            break Ok(());
+        }
+        for item in &items {
+            if let Some(index) = trees
+                .iter()
+                .position(|tree| tree.update_queue.tree == item.tree)
+            {
+                if !flushed[index] {
+                    flushed[index] = true;
+                    trees[index].profiler.begin_pass();
+                    trees[index]
+                        .update_queue
+                        .observers
+                        .notify(move |observer| observer.flush_started());
+                }
+            }
+        }
+        if passes == limit {
+            // Updates for trees that are gone are dropped as usual, and
+            // the rest stay queued for the next flush.
+            let items = items
+                .into_iter()
+                .filter(|item| trees.iter().any(|tree| tree.update_queue.tree == item.tree))
+                .collect::<Vec<_>>();
+            let mut components = items.iter().map(|item| item.component).collect::<Vec<_>>();
+            components.sort();
+            components.dedup();
+            queue.requeue(items);
+            break Err(ReactError::FlushLimit { passes, components });
+        }
+        passes += 1;
+        let mut touched = vec![false; trees.len()];
+        let mut items = items.into_iter();
+        let mut failed = None;
+        while let Some(mut item) = items.next() {
+            let index = match trees
+                .iter()
+                .position(|tree| tree.update_queue.tree == item.tree)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            touched[index] = true;
+            if let Err(err) = (item.func)(trees[index]) {
+                queue.requeue(items);
+                failed = Some(err);
+                break;
+            }
+        }
+        if let Some(err) = failed {
+            break Err(err);
+        }
+        for (tree, _) in trees
+            .iter_mut()
+            .zip(touched)
+            .filter(|&(_, touched)| touched)
+        {
+            tree.commit();
+        }
+    };
+    for (tree, _) in trees
+        .iter_mut()
+        .zip(&flushed)
+        .filter(|&(_, &flushed)| flushed)
+    {
+        tree.profiler.end_pass(PassKind::Flush);
+        tree.update_queue
+            .observers
+            .notify(move |observer| observer.flush_finished());
+    }
+    result
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{StateUpdater, VirtualTree};
+    use component::{Component, RenderContext};
+    use element::{Element, HostElement, Layer, TryDomNode};
+    use error::ReactError;
+    use std::any::type_name;
+    use std::cell::{Cell, RefCell};
+    use testing::TestNode;
+
+    /// Host element that only carries a name.
+    #[derive(Debug, PartialEq)]
+    pub struct Widget(pub &'static str);
+
+    impl HostElement for Widget {}
+
+    thread_local! {
+        static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub fn log(entry: String) {
+        LOG.with(|log| log.borrow_mut().push(entry));
+    }
+
+    /// Everything logged on this thread since the last call.
+    pub fn take_log() -> Vec<String> {
+        LOG.with(|log| log.borrow_mut().drain(..).collect())
+    }
+
+    /// Props of a `Logger`: its name, the `Logger`s below it, and a
+    /// revision to make it re-render.
+    #[derive(Clone, PartialEq)]
+    pub struct Spec {
+        pub name: &'static str,
+        pub revision: u32,
+        pub children: Vec<Spec>,
+    }
+
+    pub fn spec(name: &'static str, revision: u32, children: Vec<Spec>) -> Spec {
+        Spec {
+            name,
+            revision,
+            children,
+        }
+    }
+
+    // a
+    // +-b
+    // | +-c
+    // +-d
+    pub fn abcd(revision: u32) -> Element<Widget> {
+        Element::new_stateful::<Logger>(spec(
+            "a",
+            revision,
+            vec![
+                spec("b", revision, vec![spec("c", revision, vec![])]),
+                spec("d", revision, vec![]),
+            ],
+        ))
+    }
+
+    /// Renders its children and logs its lifecycle methods. A fragment
+    /// is used rather than a host element, since a host element that
+    /// compares equal wouldn't update its children.
+    pub struct Logger(&'static str);
+
+    impl Component<Widget> for Logger {
+        type Props = Spec;
+        type State = ();
+
+        fn create(props: &Spec) -> (Self, ()) {
+            (Logger(props.name), ())
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            let children = ctx.props.children.iter().cloned();
+            Element::new_fragment(children.map(Element::new_stateful::<Logger>).collect())
+        }
+
+        fn did_mount(&mut self) {
+            log(format!("did_mount {}", self.0));
+        }
+
+        fn did_update(&mut self) {
+            log(format!("did_update {}", self.0));
+        }
+
+        fn will_unmount(&mut self) {
+            log(format!("will_unmount {}", self.0));
+        }
+    }
+
+    #[test]
+    fn children_commit_before_their_parents() {
+        let mut tree = VirtualTree::mount(abcd(0));
+        assert_eq!(
+            take_log(),
+            vec!["did_mount c", "did_mount b", "did_mount d", "did_mount a"]
+        );
+
+        tree.update(abcd(1)).unwrap();
+        assert_eq!(
+            take_log(),
+            vec![
+                "did_update c",
+                "did_update b",
+                "did_update d",
+                "did_update a"
+            ]
+        );
+
+        // Only the nodes whose props changed commit, and a child mounted
+        // by the update does so before its parent's `did_update`.
+        tree.update(Element::new_stateful::<Logger>(spec(
+            "a",
+            2,
+            vec![
+                spec("b", 1, vec![spec("c", 1, vec![])]),
+                spec("d", 2, vec![spec("e", 2, vec![])]),
+            ],
+        )))
+        .unwrap();
+        assert_eq!(
+            take_log(),
+            vec!["did_mount e", "did_update d", "did_update a"]
+        );
+    }
+
+    /// Renders a chain of `depth` more of itself.
+    pub struct Chain;
+
+    impl Component<Widget> for Chain {
+        type Props = u32;
+        type State = ();
+
+        fn create(_props: &u32) -> (Self, ()) {
+            (Chain, ())
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            match *ctx.props {
+                0 => Element::new_host(Widget("leaf"), vec![]),
+                depth => Element::new_stateful::<Chain>(depth - 1),
+            }
+        }
+    }
+
+    #[test]
+    fn deep_and_wide_trees_commit() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Chain>(10_000));
+        tree.update(Element::new_stateful::<Chain>(10_001)).unwrap();
+        tree.unmount();
+
+        let wide = |count| {
+            let children = (0..count).map(|_| Element::new_stateful::<Chain>(1));
+            Element::new_fragment(children.collect())
+        };
+        let mut tree = VirtualTree::mount(wide(20_000));
+        tree.update(wide(20_001)).unwrap();
+        tree.unmount();
+    }
+
+    /// Counts its state up to its props, one update per commit.
+    pub struct Counter {
+        updater: RefCell<Option<StateUpdater<Widget, Counter>>>,
+        done: Cell<bool>,
+    }
+
+    impl Counter {
+        fn step(&self) {
+            if !self.done.get() {
+                if let Some(ref updater) = *self.updater.borrow() {
+                    updater.set_state(|count| count + 1);
+                }
+            }
+        }
+    }
+
+    impl Component<Widget> for Counter {
+        type Props = u32;
+        type State = u32;
+
+        fn create(_props: &u32) -> (Self, u32) {
+            let counter = Counter {
+                updater: RefCell::new(None),
+                done: Cell::new(false),
+            };
+            (counter, 0)
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            self.done.set(ctx.state >= ctx.props);
+            *self.updater.borrow_mut() = Some(ctx.updater);
+            Element::new_host(Widget("counter"), vec![])
+        }
+
+        fn did_mount(&mut self) {
+            self.step();
+        }
+
+        fn did_update(&mut self) {
+            self.step();
+        }
+    }
+
+    pub fn count(tree: &VirtualTree<Widget>) -> u32 {
+        *tree.find_components::<Counter>()[0].state()
+    }
+
+    #[test]
+    fn flush_applies_updates_scheduled_while_flushing() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Counter>(3));
+        assert_eq!(count(&tree), 0);
+        tree.flush().unwrap();
+        assert_eq!(count(&tree), 3);
+        tree.flush().unwrap();
+        assert_eq!(count(&tree), 3);
+    }
+
+    #[test]
+    fn flush_limit_keeps_the_updates_it_didnt_apply() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Counter>(100));
+        tree.set_flush_limit(5);
+        match tree.flush() {
+            Err(ReactError::FlushLimit { passes, components }) => {
+                assert_eq!(passes, 5);
+                assert_eq!(components, vec![type_name::<Counter>()]);
+            }
+            other => panic!("expected a FlushLimit error, got {:?}", other),
+        }
+        assert_eq!(count(&tree), 5);
+
+        assert!(tree.flush().is_err());
+        assert_eq!(count(&tree), 10);
+
+        tree.set_flush_limit(1000);
+        tree.flush().unwrap();
+        assert_eq!(count(&tree), 100);
+    }
+
+    #[test]
+    fn update_applies_the_element_after_hitting_the_flush_limit() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Counter>(1_000_000));
+        tree.set_flush_limit(3);
+        match tree.update(host("x", vec![])) {
+            Err(ReactError::FlushLimit { passes: 3, .. }) => {}
+            other => panic!("expected a FlushLimit error, got {:?}", other),
+        }
+        let root = tree.render::<TestNode<Widget>>().unwrap().unwrap();
+        assert_eq!(describe(&root), "x");
+
+        // The counter's leftover updates are dropped along with it.
+        tree.update(host("y", vec![])).unwrap();
+        let root = tree.render::<TestNode<Widget>>().unwrap().unwrap();
+        assert_eq!(describe(&root), "y");
+    }
+
+    pub fn host(name: &'static str, children: Vec<Element<Widget>>) -> Element<Widget> {
+        Element::new_host(Widget(name), children)
+    }
+
+    /// Host node described as `name(children...)`. Logs every node it
+    /// is asked to create, and fails for names starting with `bad`.
+    pub struct Built(pub String);
+
+    impl<'a> TryDomNode<'a> for Built {
+        type Widget = Widget;
+        type Error = String;
+
+        fn try_new_dom_node(widget: &Widget, children: Vec<Built>) -> Result<Built, String> {
+            log(widget.0.to_owned());
+            if widget.0.starts_with("bad") {
+                return Err(format!("{} failed", widget.0));
+            }
+            let mut description = widget.0.to_owned();
+            if !children.is_empty() {
+                let children = children.into_iter().map(|child| child.0);
+                description += &format!("({})", children.collect::<Vec<_>>().join(", "));
+            }
+            Ok(Built(description))
+        }
+    }
+
+    fn messages(errors: Vec<ReactError>) -> Vec<String> {
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn try_render_builds_every_node() {
+        let tree = VirtualTree::mount(host(
+            "root",
+            vec![host("a", vec![host("b", vec![])]), host("c", vec![])],
+        ));
+        assert_eq!(
+            tree.try_render::<Built>().unwrap().unwrap().0,
+            "root(a(b), c)"
+        );
+        assert_eq!(take_log(), vec!["b", "a", "c", "root"]);
+        match tree.try_render_collecting::<Built>() {
+            Ok(Some(root)) => assert_eq!(root.0, "root(a(b), c)"),
+            _ => panic!("expected a root"),
+        }
+        take_log();
+
+        let tree = VirtualTree::mount(Element::new_fragment(vec![]));
+        assert!(tree.try_render::<Built>().unwrap().is_none());
+    }
+
+    #[test]
+    fn try_render_stops_at_the_first_error() {
+        let tree = VirtualTree::mount(host(
+            "root",
+            vec![
+                host("bad1", vec![]),
+                host("a", vec![host("bad2", vec![])]),
+                host("c", vec![]),
+            ],
+        ));
+        let err = tree.try_render::<Built>().err().unwrap();
+        assert_eq!(err.to_string(), "host error: bad1 failed");
+        assert_eq!(take_log(), vec!["bad1"]);
+    }
+
+    #[test]
+    fn try_render_collecting_returns_every_error() {
+        let tree = VirtualTree::mount(host(
+            "root",
+            vec![
+                host("bad1", vec![]),
+                host("a", vec![host("bad2", vec![])]),
+                host("c", vec![]),
+            ],
+        ));
+        let errors = tree.try_render_collecting::<Built>().err().unwrap();
+        assert_eq!(
+            messages(errors),
+            vec!["host error: bad1 failed", "host error: bad2 failed"]
+        );
+        // Nodes above a failed one aren't created.
+        assert_eq!(take_log(), vec!["bad1", "bad2", "c"]);
+
+        let tree = VirtualTree::mount(Element::new_fragment(vec![
+            host("a", vec![]),
+            host("b", vec![]),
+        ]));
+        match tree.try_render_collecting::<Built>() {
+            Err(errors) => match errors[..] {
+                [ReactError::MultipleRoots { count: 2 }] => {}
+                _ => panic!("expected a MultipleRoots error, got {:?}", errors),
+            },
+            Ok(_) => panic!("expected a MultipleRoots error"),
+        }
+        take_log();
+    }
+
+    fn describe(node: &TestNode<Widget>) -> String {
+        let mut description = node.element.0.to_owned();
+        if !node.children.is_empty() {
+            let children = node.children.iter().map(describe);
+            description += &format!("({})", children.collect::<Vec<_>>().join(", "));
+        }
+        description
+    }
+
+    fn describe_layers<Dom, F>(layers: Vec<(Layer, Vec<Dom>)>, describe: F) -> Vec<String>
+    where
+        F: Fn(&Dom) -> String,
+    {
+        layers
+            .iter()
+            .map(|&(layer, ref nodes)| {
+                let nodes = nodes.iter().map(&describe).collect::<Vec<_>>();
+                format!("{}: {}", layer.name, nodes.join(", "))
+            })
+            .collect()
+    }
+
+    fn layered(extra: Vec<Element<Widget>>) -> Element<Widget> {
+        let mut children = vec![
+            host("a", vec![]),
+            Element::new_layer(Layer::new("modal", 10), vec![host("dialog", vec![])]),
+            Element::new_layer(Layer::new("below", -1), vec![host("shadow", vec![])]),
+            Element::new_layer(Layer::new("tooltip", 0), vec![host("tip", vec![])]),
+            Element::new_layer(Layer::BASE, vec![host("extra", vec![])]),
+            host(
+                "b",
+                vec![Element::new_layer(
+                    Layer::new("modal", 10),
+                    vec![host("confirm", vec![])],
+                )],
+            ),
+        ];
+        children.extend(extra);
+        host("root", children)
+    }
+
+    #[test]
+    fn render_layers_orders_layers_by_z_index() {
+        let tree = VirtualTree::mount(layered(vec![]));
+        let expected = vec![
+            "below: shadow",
+            "base: root(a, b), extra",
+            "tooltip: tip",
+            "modal: dialog, confirm",
+        ];
+        assert_eq!(
+            describe_layers(tree.render_layers::<TestNode<Widget>>(), describe),
+            expected
+        );
+        let layers = tree.try_render_layers::<Built>().unwrap();
+        assert_eq!(describe_layers(layers, |node| node.0.clone()), expected);
+        take_log();
+    }
+
+    #[test]
+    fn render_leaves_out_layer_content() {
+        let tree = VirtualTree::mount(layered(vec![]));
+        let root = tree.render::<TestNode<Widget>>().unwrap().unwrap();
+        assert_eq!(describe(&root), "root(a, b)");
+        let roots = tree.render_all::<TestNode<Widget>>();
+        assert_eq!(
+            roots.iter().map(describe).collect::<Vec<_>>(),
+            vec!["root(a, b)"]
+        );
+
+        let tree = VirtualTree::mount(Element::new_layer(
+            Layer::new("modal", 10),
+            vec![host("dialog", vec![])],
+        ));
+        assert!(tree.render::<TestNode<Widget>>().unwrap().is_none());
+        assert_eq!(
+            describe_layers(tree.render_layers::<TestNode<Widget>>(), describe),
+            vec!["base: ", "modal: dialog"]
+        );
+    }
+
+    #[test]
+    fn render_all_returns_every_top_level_host() {
+        let tree = VirtualTree::mount(Element::new_fragment(vec![
+            host("a", vec![host("x", vec![])]),
+            Element::new_fragment(vec![host("b", vec![])]),
+            host("c", vec![]),
+        ]));
+        match tree.render::<TestNode<Widget>>() {
+            Err(ReactError::MultipleRoots { count: 3 }) => {}
+            other => panic!("expected a MultipleRoots error, got {:?}", other),
+        }
+        let roots = tree.render_all::<TestNode<Widget>>();
+        assert_eq!(
+            roots.iter().map(describe).collect::<Vec<_>>(),
+            vec!["a(x)", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn try_render_layers_fails_for_layer_content() {
+        let tree = VirtualTree::mount(layered(vec![Element::new_layer(
+            Layer::new("modal", 10),
+            vec![host("bad", vec![])],
+        )]));
+        let err = tree.try_render_layers::<Built>().err().unwrap();
+        assert_eq!(err.to_string(), "host error: bad failed");
+        take_log();
     }
 }
@@ -0,0 +1,321 @@
+use element::HostElement;
+use flat_tree::NodeKey;
+use reconciler::VirtualNode;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+/// Receives an event for everything the reconciler does to a tree.
+/// Register one with `VirtualTree::add_observer`.
+///
+/// `type_name` is the component's type name for stateful nodes and
+/// `None` for host and fragment nodes.
+pub trait TreeObserver<H: HostElement> {
+    fn mounted(&mut self, _key: NodeKey<VirtualNode<H>>, _type_name: Option<&'static str>) {}
+
+    /// The node re-rendered, or its host element changed.
+    fn updated(&mut self, _key: NodeKey<VirtualNode<H>>, _type_name: Option<&'static str>) {}
+
+    fn unmounted(&mut self, _key: NodeKey<VirtualNode<H>>, _type_name: Option<&'static str>) {}
+
+    /// A component called `StateUpdater::set_state`.
+    fn update_enqueued(&mut self, _key: NodeKey<VirtualNode<H>>, _type_name: &'static str) {}
+
+    /// A queued state update was applied, just before the component
+    /// re-renders.
+    fn update_applied(&mut self, _key: NodeKey<VirtualNode<H>>, _type_name: &'static str) {}
+
+    /// `VirtualTree::flush` found queued updates to apply.
+    fn flush_started(&mut self) {}
+
+    /// `VirtualTree::flush` applied every update and committed them, or
+    /// stopped with an error.
+    fn flush_finished(&mut self) {}
+}
+
+/// The observers of one tree, shared with its state updaters so that
+/// enqueued updates can be reported as they happen.
+pub struct Observers<H: HostElement> {
+    shared: Arc<Mutex<Shared<H>>>,
+}
+
+struct Shared<H: HostElement> {
+    observers: Vec<Box<dyn TreeObserver<H>>>,
+    /// Set while the observers are out of the list being notified.
+    notifying: bool,
+    /// Events raised by the observers themselves while they were being
+    /// notified, delivered once the current event is done.
+    deferred: Vec<Event<H>>,
+}
+
+type Event<H> = Box<dyn FnMut(&mut dyn TreeObserver<H>)>;
+
+impl<H> Clone for Observers<H>
+where
+    H: HostElement,
+{
+    fn clone(&self) -> Self {
+        Observers {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<H> Observers<H>
+where
+    H: HostElement,
+{
+    pub fn new() -> Observers<H> {
+        Observers {
+            shared: Arc::new(Mutex::new(Shared {
+                observers: vec![],
+                notifying: false,
+                deferred: vec![],
+            })),
+        }
+    }
+
+    pub fn add(&self, observer: Box<dyn TreeObserver<H>>) {
+        self.shared.lock().unwrap().observers.push(observer);
+    }
+
+    /// Calls `func` on every observer. The lock isn't held while they
+    /// run, so observers can call back into the tree, for example to
+    /// `set_state`.
+    pub fn notify<Func>(&self, mut func: Func)
+    where
+        Func: FnMut(&mut dyn TreeObserver<H>) + 'static,
+    {
+        let mut observers = {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.notifying {
+                shared.deferred.push(Box::new(func));
+                return;
+            }
+            if shared.observers.is_empty() {
+                return;
+            }
+            shared.notifying = true;
+            mem::take(&mut shared.observers)
+        };
+        for observer in observers.iter_mut() {
+            func(&mut **observer);
+        }
+        loop {
+            let deferred = {
+                let mut shared = self.shared.lock().unwrap();
+                if shared.deferred.is_empty() {
+                    // Observers added while notifying go after the others.
+                    observers.append(&mut shared.observers);
+                    shared.observers = observers;
+                    shared.notifying = false;
+                    return;
+                }
+                mem::take(&mut shared.deferred)
+            };
+            for mut func in deferred {
+                for observer in observers.iter_mut() {
+                    func(&mut **observer);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeObserver;
+    use element::Element;
+    use flat_tree::NodeKey;
+    use reconciler::tests::{count, spec, take_log, Counter, Logger, Widget};
+    use reconciler::{StateUpdater, VirtualNode, VirtualTree};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Key = NodeKey<VirtualNode<Widget>>;
+
+    /// Records every event as `"<event> <key> <type>"`.
+    #[derive(Clone, Default)]
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl Recorder {
+        fn record(&self, event: &str, key: Option<Key>, type_name: Option<&'static str>) {
+            let mut entry = event.to_owned();
+            if let Some(key) = key {
+                entry += &format!(" {}v{}", key.index(), key.generation());
+            }
+            if let Some(type_name) = type_name {
+                entry += &format!(" {}", type_name.rsplit("::").next().unwrap());
+            }
+            self.0.borrow_mut().push(entry);
+        }
+
+        fn take(&self) -> Vec<String> {
+            self.0.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl TreeObserver<Widget> for Recorder {
+        fn mounted(&mut self, key: Key, type_name: Option<&'static str>) {
+            self.record("mounted", Some(key), type_name);
+        }
+
+        fn updated(&mut self, key: Key, type_name: Option<&'static str>) {
+            self.record("updated", Some(key), type_name);
+        }
+
+        fn unmounted(&mut self, key: Key, type_name: Option<&'static str>) {
+            self.record("unmounted", Some(key), type_name);
+        }
+
+        fn update_enqueued(&mut self, key: Key, type_name: &'static str) {
+            self.record("update_enqueued", Some(key), Some(type_name));
+        }
+
+        fn update_applied(&mut self, key: Key, type_name: &'static str) {
+            self.record("update_applied", Some(key), Some(type_name));
+        }
+
+        fn flush_started(&mut self) {
+            self.record("flush_started", None, None);
+        }
+
+        fn flush_finished(&mut self) {
+            self.record("flush_finished", None, None);
+        }
+    }
+
+    fn mount(element: Element<Widget>) -> (VirtualTree<Widget>, Recorder) {
+        let recorder = Recorder::default();
+        let tree = VirtualTree::mount_with_observers(element, vec![Box::new(recorder.clone())]);
+        (tree, recorder)
+    }
+
+    fn logger(revision: u32) -> Element<Widget> {
+        Element::new_fragment(vec![Element::new_stateful::<Logger>(spec(
+            "a",
+            revision,
+            vec![],
+        ))])
+    }
+
+    #[test]
+    fn reports_mount_update_replace_and_unmount() {
+        let (mut tree, recorder) = mount(logger(0));
+        assert_eq!(
+            recorder.take(),
+            vec!["mounted 0v0", "mounted 1v0 Logger", "mounted 2v0"]
+        );
+
+        tree.update(logger(1)).unwrap();
+        assert_eq!(
+            recorder.take(),
+            vec!["updated 0v0", "updated 1v0 Logger", "updated 2v0"]
+        );
+
+        // A host element takes the component's place, and the
+        // component's fragment goes with it.
+        tree.update(Element::new_fragment(vec![Element::new_host(
+            Widget("a"),
+            vec![],
+        )]))
+        .unwrap();
+        assert_eq!(
+            recorder.take(),
+            vec![
+                "updated 0v0",
+                "unmounted 1v0 Logger",
+                "mounted 1v0",
+                "unmounted 2v0"
+            ]
+        );
+
+        tree.unmount();
+        assert_eq!(recorder.take(), vec!["unmounted 1v0", "unmounted 0v0"]);
+        take_log();
+    }
+
+    #[test]
+    fn reports_flushed_updates() {
+        let (mut tree, recorder) = mount(Element::new_stateful::<Counter>(2));
+        assert_eq!(
+            recorder.take(),
+            vec![
+                "mounted 0v0 Counter",
+                "mounted 1v0",
+                "update_enqueued 0v0 Counter"
+            ]
+        );
+
+        tree.flush().unwrap();
+        assert_eq!(
+            recorder.take(),
+            vec![
+                "flush_started",
+                "update_applied 0v0 Counter",
+                "updated 0v0 Counter",
+                "update_enqueued 0v0 Counter",
+                "update_applied 0v0 Counter",
+                "updated 0v0 Counter",
+                "flush_finished",
+            ]
+        );
+
+        // Nothing is reported for a flush with nothing to apply.
+        tree.flush().unwrap();
+        assert!(recorder.take().is_empty());
+    }
+
+    /// Calls `set_state` from inside the first `flush_started` it sees,
+    /// then records it like a `Recorder`.
+    struct Nudger {
+        updater: Option<StateUpdater<Widget, Counter>>,
+        recorder: Recorder,
+    }
+
+    impl TreeObserver<Widget> for Nudger {
+        fn update_enqueued(&mut self, key: Key, type_name: &'static str) {
+            self.recorder.update_enqueued(key, type_name);
+        }
+
+        fn flush_started(&mut self) {
+            if let Some(updater) = self.updater.take() {
+                updater.set_state(|count| count + 1);
+            }
+            self.recorder.flush_started();
+        }
+    }
+
+    #[test]
+    fn defers_events_raised_while_notifying() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Counter>(1));
+        let nudger = Recorder::default();
+        let recorder = Recorder::default();
+        let updater = tree.find_components::<Counter>()[0].updater();
+        tree.add_observer(Nudger {
+            updater: Some(updater),
+            recorder: nudger.clone(),
+        });
+        tree.add_observer(recorder.clone());
+
+        // Every observer sees `flush_started` before the update that the
+        // first one enqueued while it was being notified.
+        tree.flush().unwrap();
+        assert_eq!(
+            nudger.take(),
+            vec!["flush_started", "update_enqueued 0v0 Counter"]
+        );
+        assert_eq!(
+            recorder.take(),
+            vec![
+                "flush_started",
+                "update_enqueued 0v0 Counter",
+                "update_applied 0v0 Counter",
+                "updated 0v0 Counter",
+                "update_applied 0v0 Counter",
+                "updated 0v0 Counter",
+                "flush_finished",
+            ]
+        );
+        assert_eq!(count(&tree), 2);
+    }
+}
@@ -0,0 +1,507 @@
+use element::HostElement;
+use flat_tree::NodeKey;
+use reconciler::VirtualNode;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Which `VirtualTree` method a profiled pass was made by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassKind {
+    Update,
+    Flush,
+}
+
+impl PassKind {
+    fn name(self) -> &'static str {
+        match self {
+            PassKind::Update => "update",
+            PassKind::Flush => "flush",
+        }
+    }
+}
+
+/// One call to `VirtualTree::update`, or one call to `VirtualTree::flush`
+/// that had updates to apply, including its commit phase.
+#[derive(Clone, Debug)]
+pub struct PassProfile {
+    pub kind: PassKind,
+    /// Time since profiling started.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// What one stateful node cost over the whole profile.
+pub struct NodeProfile<H: HostElement> {
+    pub key: NodeKey<VirtualNode<H>>,
+    pub type_name: &'static str,
+    pub renders: u32,
+    /// Time spent mounting or updating the node itself, which is mostly
+    /// `create`, `get_derived_state_from_props` and `render`.
+    pub render_time: Duration,
+    /// Time spent in `did_mount`, `did_update` and `will_unmount`.
+    pub lifecycle_time: Duration,
+    /// Time from the start of the node's own render until its whole
+    /// subtree had been reconciled, summed over every render.
+    pub reconcile_time: Duration,
+}
+
+impl<H> Clone for NodeProfile<H>
+where
+    H: HostElement,
+{
+    fn clone(&self) -> Self {
+        NodeProfile {
+            key: self.key,
+            type_name: self.type_name,
+            renders: self.renders,
+            render_time: self.render_time,
+            lifecycle_time: self.lifecycle_time,
+            reconcile_time: self.reconcile_time,
+        }
+    }
+}
+
+/// A span of time in a `Profile`, in the order it was recorded.
+pub struct TraceEvent<H: HostElement> {
+    /// The pass kind, component type or lifecycle method name, or
+    /// `"render"`.
+    pub name: &'static str,
+    /// One of `"pass"`, `"reconcile"`, `"render"` and `"lifecycle"`.
+    pub category: &'static str,
+    pub key: Option<NodeKey<VirtualNode<H>>>,
+    /// Time since profiling started.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Timings recorded by `VirtualTree::start_profiling`.
+pub struct Profile<H: HostElement> {
+    passes: Vec<PassProfile>,
+    nodes: HashMap<NodeKey<VirtualNode<H>>, NodeProfile<H>>,
+    events: Vec<TraceEvent<H>>,
+}
+
+impl<H> Profile<H>
+where
+    H: HostElement,
+{
+    pub fn passes(&self) -> &[PassProfile] {
+        &self.passes
+    }
+
+    /// Every stateful node that did any work, the most expensive
+    /// subtree first.
+    pub fn nodes(&self) -> Vec<&NodeProfile<H>> {
+        let mut nodes = self.nodes.values().collect::<Vec<_>>();
+        nodes.sort_by_key(|node| Reverse(node.reconcile_time));
+        nodes
+    }
+
+    pub fn events(&self) -> &[TraceEvent<H>] {
+        &self.events
+    }
+
+    pub fn node(&self, key: NodeKey<VirtualNode<H>>) -> Option<&NodeProfile<H>> {
+        self.nodes.get(&key)
+    }
+
+    fn node_mut(
+        &mut self,
+        key: NodeKey<VirtualNode<H>>,
+        type_name: &'static str,
+    ) -> &mut NodeProfile<H> {
+        self.nodes.entry(key).or_insert_with(|| NodeProfile {
+            key,
+            type_name,
+            renders: 0,
+            render_time: Duration::default(),
+            lifecycle_time: Duration::default(),
+            reconcile_time: Duration::default(),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<H> Profile<H>
+where
+    H: HostElement,
+{
+    /// The profile in the Chrome trace event format, which can be
+    /// loaded into `chrome://tracing`, Perfetto or speedscope. Passes,
+    /// subtree reconciliation, renders and lifecycle methods are all
+    /// complete (`"ph": "X"`) events on a single thread.
+    pub fn to_chrome_trace(&self) -> ::serde_json::Value {
+        fn micros(duration: Duration) -> f64 {
+            duration.as_secs() as f64 * 1e6 + f64::from(duration.subsec_nanos()) / 1e3
+        }
+
+        let events = self
+            .events
+            .iter()
+            .map(|event| {
+                let mut value = json!({
+                    "name": event.name,
+                    "cat": event.category,
+                    "ph": "X",
+                    "ts": micros(event.start),
+                    "dur": micros(event.duration),
+                    "pid": 1,
+                    "tid": 1,
+                });
+                if let Some(key) = event.key {
+                    value["args"] = json!({
+                        "key": format!("{}v{}", key.index(), key.generation()),
+                    });
+                }
+                value
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+    }
+}
+
+/// A stateful node that rendered, whose subtree is still being
+/// reconciled.
+struct Visit<H: HostElement> {
+    key: NodeKey<VirtualNode<H>>,
+    type_name: &'static str,
+    start: Duration,
+    /// When the node's own render finished.
+    end: Duration,
+}
+
+struct Recorder<H: HostElement> {
+    epoch: Instant,
+    profile: Profile<H>,
+    pass_start: Option<Duration>,
+    /// Innermost last, since subtrees finish in post-order.
+    open: Vec<Visit<H>>,
+}
+
+impl<H> Recorder<H>
+where
+    H: HostElement,
+{
+    fn new() -> Recorder<H> {
+        Recorder {
+            epoch: Instant::now(),
+            profile: Profile {
+                passes: vec![],
+                nodes: HashMap::new(),
+                events: vec![],
+            },
+            pass_start: None,
+            open: vec![],
+        }
+    }
+
+    fn elapsed(&self, instant: Instant) -> Duration {
+        instant.duration_since(self.epoch)
+    }
+}
+
+/// Collects a `Profile` while profiling is on. Every method is a no-op
+/// while it is off.
+pub struct Profiler<H: HostElement> {
+    recorder: RefCell<Option<Recorder<H>>>,
+}
+
+impl<H> Profiler<H>
+where
+    H: HostElement,
+{
+    pub fn new() -> Profiler<H> {
+        Profiler {
+            recorder: RefCell::new(None),
+        }
+    }
+
+    pub fn start(&self) {
+        *self.recorder.borrow_mut() = Some(Recorder::new());
+    }
+
+    pub fn stop(&self) -> Option<Profile<H>> {
+        self.recorder
+            .borrow_mut()
+            .take()
+            .map(|recorder| recorder.profile)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.recorder.borrow().is_some()
+    }
+
+    /// The current time, if profiling. Pass it back to the recording
+    /// methods once the work being timed is done.
+    pub fn timer(&self) -> Option<Instant> {
+        if self.is_running() {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub fn begin_pass(&self) {
+        if let Some(ref mut recorder) = *self.recorder.borrow_mut() {
+            let now = recorder.elapsed(Instant::now());
+            recorder.pass_start = Some(now);
+            // A pass that failed partway can leave subtrees unfinished.
+            recorder.open.clear();
+        }
+    }
+
+    pub fn end_pass(&self, kind: PassKind) {
+        if let Some(ref mut recorder) = *self.recorder.borrow_mut() {
+            // Profiling may have been started halfway through the pass.
+            let start = match recorder.pass_start.take() {
+                Some(start) => start,
+                None => return,
+            };
+            let duration = recorder.elapsed(Instant::now()) - start;
+            recorder.profile.passes.push(PassProfile {
+                kind,
+                start,
+                duration,
+            });
+            recorder.profile.events.push(TraceEvent {
+                name: kind.name(),
+                category: "pass",
+                key: None,
+                start,
+                duration,
+            });
+        }
+    }
+
+    /// Records that the reconciler mounted or updated `key`. `rendered`
+    /// is the type name of a stateful node that rendered, whose subtree
+    /// is timed until `finish` is called for it.
+    pub fn visit(
+        &self,
+        key: NodeKey<VirtualNode<H>>,
+        timer: Option<Instant>,
+        rendered: Option<&'static str>,
+    ) {
+        let mut recorder = self.recorder.borrow_mut();
+        if let (Some(recorder), Some(started), Some(type_name)) =
+            (recorder.as_mut(), timer, rendered)
+        {
+            let start = recorder.elapsed(started);
+            let end = recorder.elapsed(Instant::now());
+            recorder.open.push(Visit {
+                key,
+                type_name,
+                start,
+                end,
+            });
+        }
+    }
+
+    pub fn lifecycle(
+        &self,
+        key: NodeKey<VirtualNode<H>>,
+        type_name: &'static str,
+        method: &'static str,
+        timer: Option<Instant>,
+    ) {
+        let mut recorder = self.recorder.borrow_mut();
+        if let (Some(recorder), Some(started)) = (recorder.as_mut(), timer) {
+            let start = recorder.elapsed(started);
+            let duration = recorder.elapsed(Instant::now()) - start;
+            recorder.profile.node_mut(key, type_name).lifecycle_time += duration;
+            recorder.profile.events.push(TraceEvent {
+                name: method,
+                category: "lifecycle",
+                key: Some(key),
+                start,
+                duration,
+            });
+        }
+    }
+
+    /// Records that the reconciler is done with `key` and its whole
+    /// subtree. It has to be called for nodes in post-order, so the
+    /// innermost open subtree is the only one that can be done.
+    pub fn finish(&self, key: NodeKey<VirtualNode<H>>) {
+        if let Some(ref mut recorder) = *self.recorder.borrow_mut() {
+            if recorder.open.last().map(|visit| visit.key) != Some(key) {
+                return;
+            }
+            let visit = recorder.open.pop().unwrap();
+            let subtree_end = recorder.elapsed(Instant::now());
+
+            let node = recorder.profile.node_mut(visit.key, visit.type_name);
+            node.renders += 1;
+            node.render_time += visit.end - visit.start;
+            node.reconcile_time += subtree_end - visit.start;
+
+            recorder.profile.events.push(TraceEvent {
+                name: visit.type_name,
+                category: "reconcile",
+                key: Some(visit.key),
+                start: visit.start,
+                duration: subtree_end - visit.start,
+            });
+            recorder.profile.events.push(TraceEvent {
+                name: "render",
+                category: "render",
+                key: Some(visit.key),
+                start: visit.start,
+                duration: visit.end - visit.start,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PassKind, Profile, TraceEvent};
+    use component::{Component, RenderContext};
+    use element::Element;
+    use flat_tree::NodeKey;
+    use reconciler::tests::{abcd, spec, take_log, Logger, Widget};
+    use reconciler::{VirtualNode, VirtualTree};
+    use std::any::type_name;
+
+    type Key = NodeKey<VirtualNode<Widget>>;
+
+    fn event<'a>(profile: &'a Profile<Widget>, category: &str, key: Key) -> &'a TraceEvent<Widget> {
+        let mut events = profile
+            .events()
+            .iter()
+            .filter(|event| event.category == category && event.key == Some(key));
+        let event = events.next().expect("no event for the node");
+        assert!(events.next().is_none(), "several events for the node");
+        event
+    }
+
+    fn contains(outer: &TraceEvent<Widget>, inner: &TraceEvent<Widget>) -> bool {
+        outer.start <= inner.start && inner.start + inner.duration <= outer.start + outer.duration
+    }
+
+    fn logger_keys(tree: &VirtualTree<Widget>) -> Vec<Key> {
+        tree.find_components::<Logger>()
+            .iter()
+            .map(|handle| handle.key())
+            .collect()
+    }
+
+    #[test]
+    fn times_each_rendered_subtree() {
+        let mut tree = VirtualTree::mount(abcd(0));
+        let keys = logger_keys(&tree);
+        let (a, b, c, d) = (keys[0], keys[1], keys[2], keys[3]);
+
+        tree.start_profiling();
+        tree.update(abcd(1)).unwrap();
+        let profile = tree.stop_profiling().unwrap();
+        take_log();
+
+        assert_eq!(profile.passes().len(), 1);
+        assert_eq!(profile.passes()[0].kind, PassKind::Update);
+
+        let nodes = profile.nodes();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0].key, a);
+        for node in nodes {
+            assert_eq!(node.type_name, type_name::<Logger>());
+            assert_eq!(node.renders, 1);
+            assert!(node.render_time <= node.reconcile_time);
+        }
+
+        let reconcile = |key| event(&profile, "reconcile", key);
+        assert!(contains(reconcile(a), reconcile(b)));
+        assert!(contains(reconcile(b), reconcile(c)));
+        assert!(contains(reconcile(a), reconcile(d)));
+        assert!(!contains(reconcile(b), reconcile(d)));
+        for &key in &keys {
+            assert!(contains(reconcile(key), event(&profile, "render", key)));
+            assert_eq!(event(&profile, "lifecycle", key).name, "did_update");
+        }
+    }
+
+    /// Renders a `Logger` whose revision is its state.
+    struct Parent;
+
+    impl Component<Widget> for Parent {
+        type Props = ();
+        type State = u32;
+
+        fn create(_props: &()) -> (Self, u32) {
+            (Parent, 0)
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            Element::new_fragment(vec![Element::new_stateful::<Logger>(spec(
+                "child",
+                *ctx.state,
+                vec![],
+            ))])
+        }
+    }
+
+    #[test]
+    fn times_subtrees_updated_by_set_state() {
+        let mut tree = VirtualTree::mount(Element::new_stateful::<Parent>(()));
+        let parent = tree.find_components::<Parent>()[0].key();
+        let child = logger_keys(&tree)[0];
+
+        tree.start_profiling();
+        tree.find_components::<Parent>()[0]
+            .updater()
+            .set_state(|state| state + 1);
+        tree.flush().unwrap();
+        let profile = tree.stop_profiling().unwrap();
+        take_log();
+
+        assert_eq!(profile.passes().len(), 1);
+        assert_eq!(profile.passes()[0].kind, PassKind::Flush);
+        assert_eq!(profile.nodes().len(), 2);
+        assert_eq!(profile.node(parent).unwrap().renders, 1);
+        assert_eq!(profile.node(child).unwrap().renders, 1);
+        assert!(contains(
+            event(&profile, "reconcile", parent),
+            event(&profile, "reconcile", child)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn converts_to_a_chrome_trace() {
+        let mut tree = VirtualTree::mount(abcd(0));
+        tree.start_profiling();
+        tree.update(abcd(1)).unwrap();
+        let profile = tree.stop_profiling().unwrap();
+        take_log();
+
+        let trace = profile.to_chrome_trace();
+        assert_eq!(trace["displayTimeUnit"], "ms");
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), profile.events().len());
+        for (value, event) in events.iter().zip(profile.events()) {
+            assert_eq!(value["name"], event.name);
+            assert_eq!(value["cat"], event.category);
+            assert_eq!(value["ph"], "X");
+            assert_eq!(value["pid"], 1);
+            assert_eq!(value["tid"], 1);
+            match event.key {
+                Some(key) => assert_eq!(
+                    value["args"]["key"],
+                    format!("{}v{}", key.index(), key.generation())
+                ),
+                None => assert!(value.get("args").is_none()),
+            }
+        }
+
+        let pass = events.iter().find(|value| value["cat"] == "pass").unwrap();
+        assert_eq!(pass["name"], "update");
+        let micros = profile.passes()[0].duration.as_secs_f64() * 1e6;
+        assert!((pass["dur"].as_f64().unwrap() - micros).abs() < 1e-3);
+    }
+}
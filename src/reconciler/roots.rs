@@ -0,0 +1,249 @@
+use element::{DomNode, Element, HostElement};
+use error::ReactError;
+use reconciler::{flush_trees, TreeObserver, UpdateQueue, VirtualTree, DEFAULT_FLUSH_LIMIT};
+use std::collections::BTreeMap;
+
+/// Identifies a tree mounted in a `Roots`. Keys aren't reused after the
+/// tree is unmounted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RootKey(usize);
+
+/// Several independently mounted trees, such as one per window, that
+/// share an update queue so that a single `flush` applies the state
+/// updates of all of them. Each tree keeps its own observers, added
+/// through `get_mut`, since `NodeKey`s are only unique within a tree.
+pub struct Roots<H: HostElement> {
+    queue: UpdateQueue<H>,
+    roots: BTreeMap<RootKey, VirtualTree<H>>,
+    flush_limit: usize,
+}
+
+impl<H> Roots<H>
+where
+    H: HostElement,
+{
+    pub fn new() -> Self {
+        Roots {
+            queue: UpdateQueue::new(),
+            roots: BTreeMap::new(),
+            flush_limit: DEFAULT_FLUSH_LIMIT,
+        }
+    }
+
+    /// Mounts `element` as a new tree.
+    pub fn mount(&mut self, element: Element<H>) -> RootKey {
+        self.mount_with_observers(element, vec![])
+    }
+
+    /// Like `mount`, but the observers also see the initial mount. They
+    /// only receive the events of this tree.
+    pub fn mount_with_observers(
+        &mut self,
+        element: Element<H>,
+        observers: Vec<Box<dyn TreeObserver<H>>>,
+    ) -> RootKey {
+        let queue = self.queue.for_new_tree();
+        for observer in observers {
+            queue.observers.add(observer);
+        }
+        let tree = VirtualTree::mount_with_queue(element, queue);
+        let key = RootKey(tree.update_queue.tree);
+        self.roots.insert(key, tree);
+        key
+    }
+
+    /// Flushes every tree, then reconciles `element` against the tree at
+    /// `root`. Like `VirtualTree::update`, `element` is reconciled even if
+    /// the flush fails, and the flush's error is returned afterwards.
+    pub fn update(&mut self, root: RootKey, element: Element<H>) -> Result<(), ReactError> {
+        let flushed = self.flush();
+        match self.roots.get_mut(&root) {
+            Some(tree) => tree.update_root(element)?,
+            None => return Err(stale_root(root)),
+        }
+        flushed
+    }
+
+    /// Unmounts the tree at `root`. Its queued updates are dropped by the
+    /// next flush.
+    pub fn unmount(&mut self, root: RootKey) -> Result<(), ReactError> {
+        match self.roots.remove(&root) {
+            Some(tree) => {
+                tree.unmount();
+                Ok(())
+            }
+            None => Err(stale_root(root)),
+        }
+    }
+
+    /// Like `VirtualTree::render`, for the tree at `root`.
+    pub fn render<'a, Dom>(&'a self, root: RootKey) -> Result<Option<Dom>, ReactError>
+    where
+        Dom: DomNode<'a, Widget = H>,
+    {
+        self.get(root).ok_or_else(|| stale_root(root))?.render()
+    }
+
+    /// Applies the queued state updates of every tree until the queue
+    /// stays empty. The flush limit counts passes over all of them.
+    pub fn flush(&mut self) -> Result<(), ReactError> {
+        enter_span!(debug_span!("flush"));
+        let mut trees = self.roots.values_mut().collect::<Vec<_>>();
+        flush_trees(&self.queue, self.flush_limit, &mut trees, true)
+    }
+
+    /// Like `VirtualTree::set_flush_limit`, for `Roots::flush`.
+    pub fn set_flush_limit(&mut self, limit: usize) {
+        self.flush_limit = limit;
+    }
+
+    /// The tree at `root`, for inspecting or profiling it. Its own
+    /// `flush` only applies updates scheduled by its nodes.
+    pub fn get(&self, root: RootKey) -> Option<&VirtualTree<H>> {
+        self.roots.get(&root)
+    }
+
+    pub fn get_mut(&mut self, root: RootKey) -> Option<&mut VirtualTree<H>> {
+        self.roots.get_mut(&root)
+    }
+
+    /// The mounted trees, in the order they were mounted.
+    pub fn keys(&self) -> Vec<RootKey> {
+        self.roots.keys().cloned().collect()
+    }
+}
+
+impl<H> Default for Roots<H>
+where
+    H: HostElement,
+{
+    fn default() -> Self {
+        Roots::new()
+    }
+}
+
+fn stale_root(root: RootKey) -> ReactError {
+    ReactError::StaleRoot { id: root.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RootKey, Roots};
+    use element::Element;
+    use error::ReactError;
+    use reconciler::tests::{count, Counter, Widget};
+    use testing::TestNode;
+
+    fn counter(roots: &Roots<Widget>, root: RootKey) -> u32 {
+        count(roots.get(root).unwrap())
+    }
+
+    /// Queues an update that adds `amount` to the `Counter` at `root`.
+    fn add(roots: &Roots<Widget>, root: RootKey, amount: u32) {
+        roots.get(root).unwrap().find_components::<Counter>()[0]
+            .updater()
+            .set_state(move |count| count + amount);
+    }
+
+    fn is_stale_root<T>(result: Result<T, ReactError>) -> bool {
+        matches!(result, Err(ReactError::StaleRoot { .. }))
+    }
+
+    #[test]
+    fn one_flush_services_every_root() {
+        let mut roots = Roots::new();
+        let a = roots.mount(Element::new_stateful::<Counter>(2));
+        let b = roots.mount(Element::new_stateful::<Counter>(3));
+        assert_eq!(roots.keys(), vec![a, b]);
+
+        roots.flush().unwrap();
+        assert_eq!(counter(&roots, a), 2);
+        assert_eq!(counter(&roots, b), 3);
+    }
+
+    #[test]
+    fn updates_go_to_the_tree_that_scheduled_them() {
+        // Both counters sit at the same `NodeKey` in their own tree.
+        let mut roots = Roots::new();
+        let a = roots.mount(Element::new_stateful::<Counter>(0));
+        let b = roots.mount(Element::new_stateful::<Counter>(0));
+
+        add(&roots, a, 5);
+        roots.flush().unwrap();
+        assert_eq!(counter(&roots, a), 5);
+        assert_eq!(counter(&roots, b), 0);
+
+        // A tree's own flush leaves the other trees' updates queued.
+        add(&roots, a, 1);
+        add(&roots, b, 1);
+        roots.get_mut(a).unwrap().flush().unwrap();
+        assert_eq!(counter(&roots, a), 6);
+        assert_eq!(counter(&roots, b), 0);
+
+        // Updating one root flushes all of them first.
+        roots
+            .update(a, Element::new_stateful::<Counter>(0))
+            .unwrap();
+        assert_eq!(counter(&roots, b), 1);
+    }
+
+    #[test]
+    fn unmounting_a_root_leaves_the_others_working() {
+        let mut roots = Roots::new();
+        let a = roots.mount(Element::new_stateful::<Counter>(0));
+        let b = roots.mount(Element::new_stateful::<Counter>(0));
+        add(&roots, a, 1);
+        add(&roots, b, 1);
+
+        roots.unmount(a).unwrap();
+        assert_eq!(roots.keys(), vec![b]);
+        // The unmounted tree's update is dropped.
+        roots.flush().unwrap();
+        assert_eq!(counter(&roots, b), 1);
+
+        roots
+            .update(
+                b,
+                Element::new_fragment(vec![Element::new_host(Widget("b"), vec![])]),
+            )
+            .unwrap();
+        let rendered = roots.render::<TestNode<Widget>>(b).unwrap().unwrap();
+        assert_eq!(*rendered.element, Widget("b"));
+
+        assert!(roots.get(a).is_none());
+        assert!(is_stale_root(roots.unmount(a)));
+        assert!(is_stale_root(
+            roots.update(a, Element::new_fragment(vec![]))
+        ));
+        assert!(is_stale_root(roots.render::<TestNode<Widget>>(a)));
+    }
+
+    #[test]
+    fn flush_limit_counts_passes_over_every_root() {
+        let mut roots = Roots::new();
+        let a = roots.mount(Element::new_stateful::<Counter>(100));
+        let b = roots.mount(Element::new_stateful::<Counter>(100));
+        roots.set_flush_limit(5);
+        match roots.flush() {
+            Err(ReactError::FlushLimit { passes, .. }) => assert_eq!(passes, 5),
+            other => panic!("expected a FlushLimit error, got {:?}", other),
+        }
+        assert_eq!(counter(&roots, a), 5);
+        assert_eq!(counter(&roots, b), 5);
+
+        // Updating a root still applies the element, and the leftover
+        // updates of the other root stay queued.
+        let leaf = Element::new_fragment(vec![Element::new_host(Widget("a"), vec![])]);
+        match roots.update(a, leaf) {
+            Err(ReactError::FlushLimit { .. }) => {}
+            other => panic!("expected a FlushLimit error, got {:?}", other),
+        }
+        let rendered = roots.render::<TestNode<Widget>>(a).unwrap().unwrap();
+        assert_eq!(*rendered.element, Widget("a"));
+        assert_eq!(counter(&roots, b), 10);
+
+        roots.set_flush_limit(1000);
+        roots.flush().unwrap();
+        assert_eq!(counter(&roots, b), 100);
+    }
+}
@@ -3,9 +3,11 @@ use component::RenderContext;
 use element::Element;
 use element::{HostElement, StatefulElement};
 use flat_tree::NodeChildren;
+use reconciler::component_stack;
 use reconciler::GenericStateUpdater;
+use reconciler::RenderReason;
 use reconciler::{StatefulElementWrapper, VirtualNode};
-use std::any::Any;
+use std::any::{type_name, Any};
 use std::clone::Clone;
 use std::marker::PhantomData;
 
@@ -18,9 +20,21 @@ where
     props: Class::Props,
     state: Option<Class::State>,
     children: NodeChildren<VirtualNode<H>>,
+    pending_commit: Option<PendingCommit>,
+    render_reason: RenderReason,
+    /// How many times the component has rendered since it was mounted.
+    renders: u32,
     _phantom: PhantomData<H>,
 }
 
+/// Lifecycle method owed to a component once the render phase that
+/// touched it has been applied to the tree.
+#[derive(Clone, Copy, PartialEq)]
+enum PendingCommit {
+    Mount,
+    Update,
+}
+
 pub trait StatefulNodeWrapper<H: HostElement> {
     fn mount(&mut self, updater: GenericStateUpdater<H>) -> Element<H>;
     fn update(
@@ -29,6 +43,15 @@ pub trait StatefulNodeWrapper<H: HostElement> {
         updater: GenericStateUpdater<H>,
     ) -> Result<Option<Element<H>>, Element<H>>;
     fn unmount(&mut self, updater: GenericStateUpdater<H>);
+    /// Runs the lifecycle method owed since the last commit, returning
+    /// its name.
+    fn commit(&mut self) -> Option<&'static str>;
+    fn has_pending_commit(&self) -> bool;
+    fn type_name(&self) -> &'static str;
+    fn render_reason(&self) -> RenderReason;
+    fn render_count(&self) -> u32;
+    fn debug_props(&self) -> Option<String>;
+    fn debug_state(&self) -> Option<String>;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
     fn get_children(&self) -> &NodeChildren<VirtualNode<H>>;
@@ -40,33 +63,77 @@ where
     H: HostElement,
     Class: Component<H> + 'static,
 {
+    pub fn component(&self) -> &Class {
+        &self.component
+    }
+
+    pub fn props(&self) -> &Class::Props {
+        &self.props
+    }
+
+    pub fn state(&self) -> &Class::State {
+        self.state.as_ref().unwrap()
+    }
+
     pub fn update_state<Func>(&mut self, func: Func, updater: GenericStateUpdater<H>) -> Element<H>
     where
         Func: FnOnce(Class::State) -> Class::State,
     {
         self.state = Some((func)(self.state.take().unwrap()));
-        let element = self.component.render(RenderContext {
+        self.render_reason = RenderReason::StateUpdate;
+        self.schedule_commit(PendingCommit::Update);
+        self.render(updater)
+    }
+
+    fn render(&mut self, updater: GenericStateUpdater<H>) -> Element<H> {
+        self.renders += 1;
+        enter_span!(trace_span!(
+            "render_component",
+            component = type_name::<Class>()
+        ));
+        self.component.render(RenderContext {
             props: &self.props,
             state: self.state.as_ref().unwrap(),
             updater: updater.specialize(),
-        });
-        element
+        })
+    }
+
+    fn schedule_commit(&mut self, commit: PendingCommit) {
+        // A node that was mounted earlier in the same pass only owes
+        // `did_mount`.
+        if self.pending_commit.is_none() {
+            self.pending_commit = Some(commit);
+        }
     }
 }
 
+/// Tells a real change of props apart from props that only compare
+/// unequal, using `Component::debug_props`. Formatting props can be
+/// slow, so release builds skip the check.
+fn props_change<H, Class>(old: &Class::Props, new: &Class::Props) -> RenderReason
+where
+    H: HostElement,
+    Class: Component<H>,
+{
+    if cfg!(debug_assertions) {
+        if let Some(old) = Class::debug_props(old) {
+            if Class::debug_props(new) == Some(old) {
+                return RenderReason::UnstableProps;
+            }
+        }
+    }
+    RenderReason::PropsChanged
+}
+
 impl<H, Class> StatefulNodeWrapper<H> for StatefulNode<H, Class>
 where
     H: HostElement,
     Class: Component<H> + 'static,
 {
     fn mount(&mut self, updater: GenericStateUpdater<H>) -> Element<H> {
-        let element = self.component.render(RenderContext {
-            props: &self.props,
-            state: self.state.as_ref().unwrap(),
-            updater: updater.specialize(),
-        });
+        let element = self.render(updater);
 
-        self.component.did_mount();
+        self.schedule_commit(PendingCommit::Mount);
 
         element
     }
@@ -76,13 +143,17 @@ where
         element: Element<H>,
         updater: GenericStateUpdater<H>,
     ) -> Result<Option<Element<H>>, Element<H>> {
+        component_stack::name(type_name::<Class>());
         match element {
             Element::Host { .. } => Err(element),
             Element::Fragment(_) => Err(element),
+            Element::Layer { .. } => Err(element),
             Element::Stateful(element) => {
                 match element.as_any().downcast_ref::<StatefulElement<H, Class>>() {
                     Some(element) => {
                         if self.props != element.props {
+                            self.render_reason =
+                                props_change::<H, Class>(&self.props, &element.props);
                             self.props = element.props.clone();
 
                             self.state = Some(Class::get_derived_state_from_props(
@@ -90,11 +161,9 @@ where
                                 self.state.take().unwrap(),
                             ));
 
-                            let element = self.component.render(RenderContext {
-                                props: &self.props,
-                                state: self.state.as_ref().unwrap(),
-                                updater: updater.specialize(),
-                            });
+                            let element = self.render(updater);
+
+                            self.schedule_commit(PendingCommit::Update);
 
                             Ok(Some(element))
                         } else {
@@ -112,6 +181,44 @@ where
         self.component.will_unmount();
     }
 
+    fn commit(&mut self) -> Option<&'static str> {
+        match self.pending_commit.take() {
+            Some(PendingCommit::Mount) => {
+                self.component.did_mount();
+                Some("did_mount")
+            }
+            Some(PendingCommit::Update) => {
+                self.component.did_update();
+                Some("did_update")
+            }
+            None => None,
+        }
+    }
+
+    fn has_pending_commit(&self) -> bool {
+        self.pending_commit.is_some()
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<Class>()
+    }
+
+    fn render_reason(&self) -> RenderReason {
+        self.render_reason
+    }
+
+    fn render_count(&self) -> u32 {
+        self.renders
+    }
+
+    fn debug_props(&self) -> Option<String> {
+        Class::debug_props(&self.props)
+    }
+
+    fn debug_state(&self) -> Option<String> {
+        self.state.as_ref().and_then(Class::debug_state)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -135,6 +242,7 @@ where
     Class: Component<H> + 'static,
 {
     fn create_node(&self) -> Box<dyn StatefulNodeWrapper<H>> {
+        component_stack::name(type_name::<Class>());
         let (component, initial_state) = Class::create(&self.props);
 
         Box::new(StatefulNode {
@@ -142,6 +250,9 @@ where
             props: self.props.clone(),
             state: Some(initial_state),
             children: NodeChildren::new(),
+            pending_commit: None,
+            render_reason: RenderReason::Mount,
+            renders: 0,
             _phantom: PhantomData,
         })
     }
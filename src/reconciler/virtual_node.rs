@@ -1,14 +1,19 @@
 use super::HostNode;
 use super::StatefulNodeWrapper;
-use element::DomNode;
-use element::{Element, HostElement};
+use element::{DomNode, TryDomNode};
+use element::{Element, HostElement, Layer};
 use flat_tree::NodeChildren;
 use reconciler::GenericStateUpdater;
 
+/// The updated node and the elements to reconcile its children against,
+/// or the node and element that didn't match.
+type Updated<H> = Result<(VirtualNode<H>, Option<Vec<Element<H>>>), (VirtualNode<H>, Element<H>)>;
+
 pub enum VirtualNode<H: HostElement> {
     Host(HostNode<H>),
     Stateful(Box<dyn StatefulNodeWrapper<H>>),
     Fragment(NodeChildren<VirtualNode<H>>),
+    Layer(Layer, NodeChildren<VirtualNode<H>>),
 }
 
 impl<H> VirtualNode<H>
@@ -24,6 +29,9 @@ where
                 (VirtualNode::Host(HostNode::mount(element)), children)
             }
             Element::Fragment(children) => (VirtualNode::Fragment(NodeChildren::new()), children),
+            Element::Layer { layer, children } => {
+                (VirtualNode::Layer(layer, NodeChildren::new()), children)
+            }
             Element::Stateful(node_creator) => {
                 let mut node = node_creator.create_node();
                 let children = node.mount(updater);
@@ -32,11 +40,13 @@ where
         }
     }
 
+    /// Updates `node` in place, or gives `node` and `element` back if
+    /// they're of different kinds and the node has to be replaced.
     pub fn update(
         node: VirtualNode<H>,
         element: Element<H>,
         updater: GenericStateUpdater<H>,
-    ) -> (VirtualNode<H>, Option<Vec<Element<H>>>) {
+    ) -> Updated<H> {
         match (node, element) {
             (
                 VirtualNode::Host(HostNode {
@@ -54,29 +64,27 @@ where
                     children,
                 });
                 if should_update {
-                    (node, Some(element_children))
+                    Ok((node, Some(element_children)))
                 } else {
-                    (node, None)
-                }
-            }
-            (VirtualNode::Stateful(mut node), element) => {
-                match node.update(element, updater.clone()) {
-                    Ok(Some(element)) => (VirtualNode::Stateful(node), Some(vec![element])),
-                    Ok(None) => (VirtualNode::Stateful(node), None),
-                    Err(element) => {
-                        node.unmount(updater.clone());
-                        let (node, children) = VirtualNode::mount(element, updater);
-                        (node, Some(children))
-                    }
+                    Ok((node, None))
                 }
             }
-            (old_node, new_element) => {
-                // If they're not compatible, we have to unmount and
-                // remount.
-                VirtualNode::unmount(old_node, updater.clone());
-                let (node, children) = VirtualNode::mount(new_element, updater);
-                (node, Some(children))
+            (VirtualNode::Fragment(children), Element::Fragment(element_children)) => {
+                Ok((VirtualNode::Fragment(children), Some(element_children)))
             }
+            (
+                VirtualNode::Layer(_, children),
+                Element::Layer {
+                    layer,
+                    children: element_children,
+                },
+            ) => Ok((VirtualNode::Layer(layer, children), Some(element_children))),
+            (VirtualNode::Stateful(mut node), element) => match node.update(element, updater) {
+                Ok(Some(element)) => Ok((VirtualNode::Stateful(node), Some(vec![element]))),
+                Ok(None) => Ok((VirtualNode::Stateful(node), None)),
+                Err(element) => Err((VirtualNode::Stateful(node), element)),
+            },
+            (old_node, new_element) => Err((old_node, new_element)),
         }
     }
 
@@ -85,9 +93,36 @@ where
             VirtualNode::Host(_) => (),
             VirtualNode::Stateful(mut node) => node.unmount(updater),
             VirtualNode::Fragment(_) => (),
+            VirtualNode::Layer(..) => (),
+        }
+    }
+
+    pub fn commit(&mut self) -> Option<&'static str> {
+        match *self {
+            VirtualNode::Host(_) => None,
+            VirtualNode::Stateful(ref mut node) => node.commit(),
+            VirtualNode::Fragment(_) => None,
+            VirtualNode::Layer(..) => None,
+        }
+    }
+
+    pub fn has_pending_commit(&self) -> bool {
+        match *self {
+            VirtualNode::Stateful(ref node) => node.has_pending_commit(),
+            _ => false,
+        }
+    }
+
+    /// The component type of a stateful node.
+    pub fn type_name(&self) -> Option<&'static str> {
+        match *self {
+            VirtualNode::Stateful(ref node) => Some(node.type_name()),
+            _ => None,
         }
     }
 
+    /// The host nodes this node adds to its parent. A layer's children
+    /// are drawn in the layer instead, so it adds none.
     pub fn render<'a, Dom>(&'a self, children: Vec<Dom>) -> Vec<Dom>
     where
         Dom: DomNode<'a, Widget = H>,
@@ -99,6 +134,19 @@ where
             },
             VirtualNode::Stateful(_) => children,
             VirtualNode::Fragment(_) => children,
+            VirtualNode::Layer(..) => vec![],
+        }
+    }
+
+    pub fn try_render<'a, Dom>(&'a self, children: Vec<Dom>) -> Result<Vec<Dom>, Dom::Error>
+    where
+        Dom: TryDomNode<'a, Widget = H>,
+    {
+        match *self {
+            VirtualNode::Host(ref node) => node.try_render(children).map(|dom| vec![dom]),
+            VirtualNode::Stateful(_) => Ok(children),
+            VirtualNode::Fragment(_) => Ok(children),
+            VirtualNode::Layer(..) => Ok(vec![]),
         }
     }
 }
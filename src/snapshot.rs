@@ -0,0 +1,395 @@
+//! Stable textual snapshots of mounted and rendered trees, for golden
+//! file tests.
+//!
+//! `tree_to_text` describes every node in a `VirtualTree`, including
+//! stateful components and fragments. `rendered_to_text` describes only
+//! what a host would receive from `VirtualTree::render`. Both write one
+//! node per line, indented by two spaces per level, and never include
+//! `NodeKey`s, so the output only changes when the tree does.
+//!
+//! With the `serde` feature, `tree_to_json` and `rendered_to_json`
+//! produce the same information as JSON.
+
+use element::HostElement;
+use reconciler::{InspectedNode, NodeKind, VirtualTree};
+use std::env;
+use std::fmt;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use testing::TestNode;
+
+fn describe<H>(node: &InspectedNode<H>) -> String
+where
+    H: HostElement + fmt::Debug,
+{
+    match node.kind {
+        NodeKind::Host => format!("{:?}", node.element.unwrap()),
+        NodeKind::Fragment => "Fragment".to_owned(),
+        NodeKind::Layer => {
+            let layer = node.layer.unwrap();
+            format!("Layer {} z={}", layer.name, layer.z_index)
+        }
+        NodeKind::Stateful => {
+            let mut line = format!("<{}>", node.type_name.unwrap());
+            if let Some(ref props) = node.props {
+                write!(line, " props={}", props).unwrap();
+            }
+            if let Some(ref state) = node.state {
+                write!(line, " state={}", state).unwrap();
+            }
+            line
+        }
+    }
+}
+
+fn push_line(text: &mut String, depth: usize, line: &str) {
+    for _ in 0..depth {
+        text.push_str("  ");
+    }
+    text.push_str(line);
+    text.push('\n');
+}
+
+/// Describes every mounted node, in pre-order. Host elements are
+/// written with their `Debug` output, and stateful components with
+/// their type name and, when the component provides them, the results
+/// of `Component::debug_props` and `Component::debug_state`.
+pub fn tree_to_text<H>(tree: &VirtualTree<H>) -> String
+where
+    H: HostElement + fmt::Debug,
+{
+    let mut text = String::new();
+    for node in tree.inspect() {
+        push_line(&mut text, node.depth, &describe(&node));
+    }
+    text
+}
+
+/// Describes the host elements that `VirtualTree::render_all` would
+/// pass to `DomNode::new_dom_node`. Content rendered into other layers
+/// isn't included.
+pub fn rendered_to_text<H>(tree: &VirtualTree<H>) -> String
+where
+    H: HostElement + fmt::Debug,
+{
+    let mut text = String::new();
+    let roots = tree.render_all::<TestNode<H>>();
+    let mut stack = roots.iter().rev().map(|node| (0, node)).collect::<Vec<_>>();
+    while let Some((depth, node)) = stack.pop() {
+        push_line(&mut text, depth, &format!("{:?}", node.element));
+        stack.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+    }
+    text
+}
+
+#[cfg(feature = "serde")]
+mod json {
+    use super::describe;
+    use element::HostElement;
+    use reconciler::{NodeKind, VirtualTree};
+    use serde_json::Value;
+    use std::fmt;
+    use testing::TestNode;
+
+    fn attach(stack: &mut Vec<(usize, Value)>, roots: &mut Vec<Value>, depth: usize) {
+        while matches!(stack.last(), Some(&(top, _)) if top >= depth) {
+            let (_, node) = stack.pop().unwrap();
+            match stack.last_mut() {
+                Some(&mut (_, ref mut parent)) => {
+                    parent["children"].as_array_mut().unwrap().push(node)
+                }
+                None => roots.push(node),
+            }
+        }
+    }
+
+    /// JSON form of `tree_to_text`. Each node is an object with `kind`,
+    /// `description` and `children`.
+    pub fn tree_to_json<H>(tree: &VirtualTree<H>) -> Value
+    where
+        H: HostElement + fmt::Debug,
+    {
+        let mut roots = vec![];
+        let mut stack = vec![];
+        for node in tree.inspect() {
+            attach(&mut stack, &mut roots, node.depth);
+            let kind = match node.kind {
+                NodeKind::Host => "host",
+                NodeKind::Stateful => "stateful",
+                NodeKind::Fragment => "fragment",
+                NodeKind::Layer => "layer",
+            };
+            stack.push((
+                node.depth,
+                json!({
+                    "kind": kind,
+                    "description": describe(&node),
+                    "children": [],
+                }),
+            ));
+        }
+        attach(&mut stack, &mut roots, 0);
+        roots.pop().unwrap_or(Value::Null)
+    }
+
+    fn rendered_node_to_json<H>(node: &TestNode<H>) -> Value
+    where
+        H: HostElement + fmt::Debug,
+    {
+        json!({
+            "element": format!("{:?}", node.element),
+            "children": node.children.iter().map(rendered_node_to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// JSON form of `rendered_to_text`: an array of the top-level host
+    /// nodes, each an object with `element` and `children`.
+    pub fn rendered_to_json<H>(tree: &VirtualTree<H>) -> Value
+    where
+        H: HostElement + fmt::Debug,
+    {
+        let roots = tree.render_all::<TestNode<H>>();
+        Value::Array(roots.iter().map(rendered_node_to_json).collect())
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use self::json::{rendered_to_json, tree_to_json};
+
+/// Set this environment variable to rewrite golden files instead of
+/// comparing against them.
+pub const UPDATE_ENV_VAR: &str = "REACT_RS_UPDATE_SNAPSHOTS";
+
+/// Compares `actual` against the golden file at `path`, panicking with
+/// a line diff if they differ, or if the file is missing. Golden files
+/// are only written when `REACT_RS_UPDATE_SNAPSHOTS` is set.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    assert_golden_with(path.as_ref(), actual, env::var_os(UPDATE_ENV_VAR).is_some());
+}
+
+/// `assert_golden`, with whether to rewrite the golden file passed in
+/// rather than read from the environment.
+fn assert_golden_with(path: &Path, actual: &str, update: bool) {
+    if update {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(path, actual)
+            .unwrap_or_else(|err| panic!("couldn't write {}: {}", path.display(), err));
+        return;
+    }
+
+    if !path.exists() {
+        panic!(
+            "snapshot {} doesn't exist\nrerun with {}=1 to create it",
+            path.display(),
+            UPDATE_ENV_VAR
+        );
+    }
+    let expected = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("couldn't read {}: {}", path.display(), err));
+    if expected != actual {
+        panic!(
+            "snapshot {} doesn't match (- expected, + actual):\n{}\nrerun with {}=1 to accept the new output",
+            path.display(),
+            diff_lines(&expected, actual),
+            UPDATE_ENV_VAR
+        );
+    }
+}
+
+/// Line diff based on the longest common subsequence of the two texts.
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+
+    // common[i][j] is the length of the LCS of old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            writeln!(diff, "  {}", old[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            writeln!(diff, "- {}", old[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(diff, "+ {}", new[j]).unwrap();
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_golden_with, diff_lines, rendered_to_text, tree_to_text};
+    use element::Element;
+    use reconciler::tests::{spec, Logger, Widget};
+    use reconciler::VirtualTree;
+    use std::any::type_name;
+    use std::env;
+    use std::fs;
+    use std::panic;
+    use std::path::Path;
+    use std::process;
+
+    fn mount() -> VirtualTree<Widget> {
+        VirtualTree::mount(Element::new_host(
+            Widget("root"),
+            vec![
+                Element::new_stateful::<Logger>(spec("a", 0, vec![spec("b", 0, vec![])])),
+                Element::new_host(
+                    Widget("leaf"),
+                    vec![Element::new_host(Widget("inner"), vec![])],
+                ),
+            ],
+        ))
+    }
+
+    #[test]
+    fn describes_the_mounted_tree() {
+        let logger = type_name::<Logger>();
+        assert_eq!(
+            tree_to_text(&mount()),
+            format!(
+                "Widget(\"root\")\n  <{0}>\n    Fragment\n      <{0}>\n        Fragment\n  Widget(\"leaf\")\n    Widget(\"inner\")\n",
+                logger
+            )
+        );
+    }
+
+    #[test]
+    fn describes_the_rendered_tree() {
+        assert_eq!(
+            rendered_to_text(&mount()),
+            "Widget(\"root\")\n  Widget(\"leaf\")\n    Widget(\"inner\")\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn describes_trees_as_json() {
+        use super::{rendered_to_json, tree_to_json};
+
+        let tree: VirtualTree<Widget> = VirtualTree::mount(Element::new_host(
+            Widget("root"),
+            vec![Element::new_fragment(vec![Element::new_host(
+                Widget("leaf"),
+                vec![],
+            )])],
+        ));
+        assert_eq!(
+            tree_to_json(&tree),
+            json!({
+                "kind": "host",
+                "description": "Widget(\"root\")",
+                "children": [{
+                    "kind": "fragment",
+                    "description": "Fragment",
+                    "children": [{
+                        "kind": "host",
+                        "description": "Widget(\"leaf\")",
+                        "children": [],
+                    }],
+                }],
+            })
+        );
+        assert_eq!(
+            rendered_to_json(&tree),
+            json!([{
+                "element": "Widget(\"root\")",
+                "children": [{ "element": "Widget(\"leaf\")", "children": [] }],
+            }])
+        );
+
+        let tree: VirtualTree<Widget> = VirtualTree::mount(Element::new_fragment(vec![]));
+        assert_eq!(rendered_to_json(&tree), json!([]));
+    }
+
+    fn mount_fragment() -> VirtualTree<Widget> {
+        VirtualTree::mount(Element::new_fragment(vec![
+            Element::new_host(
+                Widget("a"),
+                vec![Element::new_host(Widget("inner"), vec![])],
+            ),
+            Element::new_host(Widget("b"), vec![]),
+        ]))
+    }
+
+    #[test]
+    fn describes_every_rendered_root() {
+        assert_eq!(
+            rendered_to_text(&mount_fragment()),
+            "Widget(\"a\")\n  Widget(\"inner\")\nWidget(\"b\")\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn describes_every_rendered_root_as_json() {
+        use super::rendered_to_json;
+
+        assert_eq!(
+            rendered_to_json(&mount_fragment()),
+            json!([
+                {
+                    "element": "Widget(\"a\")",
+                    "children": [{ "element": "Widget(\"inner\")", "children": [] }],
+                },
+                { "element": "Widget(\"b\")", "children": [] },
+            ])
+        );
+    }
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(diff_lines("a\nb\nc\n", "a\nb\nc\n"), "  a\n  b\n  c\n");
+        assert_eq!(diff_lines("a\nb\nc\n", "a\nc\nd\n"), "  a\n- b\n  c\n+ d\n");
+        assert_eq!(diff_lines("", "a\n"), "+ a\n");
+        assert_eq!(diff_lines("a\n", ""), "- a\n");
+    }
+
+    /// The message `assert_golden` panics with, if it panics, when the
+    /// update variable isn't set.
+    fn golden_failure(path: &Path, actual: &str) -> Option<String> {
+        panic::catch_unwind(|| assert_golden_with(path, actual, false))
+            .err()
+            .map(|err| match err.downcast::<String>() {
+                Ok(message) => *message,
+                Err(_) => panic!("assert_golden should panic with a message"),
+            })
+    }
+
+    #[test]
+    fn compares_against_golden_files() {
+        let dir = env::temp_dir().join(format!("react-rs-golden-{}", process::id()));
+        let path = dir.join("tree.txt");
+
+        let missing = golden_failure(&path, "a\n").unwrap();
+        assert!(missing.contains("doesn't exist"), "{}", missing);
+        assert!(!path.exists());
+
+        assert_golden_with(&path, "a\nb\n", true);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+
+        assert_eq!(golden_failure(&path, "a\nb\n"), None);
+        let mismatch = golden_failure(&path, "a\nc\n").unwrap();
+        assert!(mismatch.contains("  a\n- b\n+ c\n"), "{}", mismatch);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
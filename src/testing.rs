@@ -0,0 +1,344 @@
+//! Helpers for unit testing components without a real host.
+//!
+//! `TestRenderer` owns a `VirtualTree` and can be queried for host
+//! elements and component state directly, so a test doesn't need its
+//! own `DomNode` type:
+//!
+//! ```ignore
+//! let mut renderer = TestRenderer::mount(Element::new_stateful::<Counter>(()));
+//! renderer.fire(is_button, |button| press(button));
+//! assert_eq!(*renderer.state::<Counter>(), 1);
+//! ```
+
+use component::Component;
+use element::{DomNode, Element, HostElement};
+use reconciler::VirtualTree;
+use snapshot;
+use std::any::type_name;
+use std::fmt;
+
+/// Recording host: a rendered host element together with its rendered
+/// children. `snapshot` uses it to capture what `render_all` returns.
+pub struct TestNode<'a, H: 'a> {
+    pub element: &'a H,
+    pub children: Vec<TestNode<'a, H>>,
+}
+
+impl<'a, H> DomNode<'a> for TestNode<'a, H>
+where
+    H: HostElement,
+{
+    type Widget = H;
+
+    fn new_dom_node(element: &'a H, children: Vec<Self>) -> Self {
+        TestNode { element, children }
+    }
+}
+
+impl<'a, H> TestNode<'a, H>
+where
+    H: HostElement,
+{
+    /// Every node in this subtree matching `predicate`, in pre-order.
+    pub fn find_all<P>(&self, predicate: P) -> Vec<&TestNode<'a, H>>
+    where
+        P: Fn(&H) -> bool,
+    {
+        let mut found = vec![];
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if predicate(node.element) {
+                found.push(node);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        found
+    }
+
+    pub fn find<P>(&self, predicate: P) -> Option<&TestNode<'a, H>>
+    where
+        P: Fn(&H) -> bool,
+    {
+        self.find_all(predicate).into_iter().next()
+    }
+}
+
+impl<'a, H> fmt::Debug for TestNode<'a, H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TestNode")
+            .field("element", self.element)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+pub struct TestRenderer<H: HostElement> {
+    tree: VirtualTree<H>,
+}
+
+impl<H> TestRenderer<H>
+where
+    H: HostElement,
+{
+    pub fn mount(element: Element<H>) -> TestRenderer<H> {
+        TestRenderer {
+            tree: VirtualTree::mount(element),
+        }
+    }
+
+    pub fn tree(&self) -> &VirtualTree<H> {
+        &self.tree
+    }
+
+    pub fn tree_mut(&mut self) -> &mut VirtualTree<H> {
+        &mut self.tree
+    }
+
+    /// Panics if the update fails.
+    pub fn update(&mut self, element: Element<H>) {
+        self.tree
+            .update(element)
+            .unwrap_or_else(|err| panic!("update failed: {}", err));
+    }
+
+    /// Panics if the flush fails.
+    pub fn flush(&mut self) {
+        self.tree
+            .flush()
+            .unwrap_or_else(|err| panic!("flush failed: {}", err));
+    }
+
+    pub fn unmount(self) {
+        self.tree.unmount();
+    }
+
+    /// What a host would currently be given by `VirtualTree::render`.
+    ///
+    /// Panics if rendering fails.
+    pub fn rendered(&self) -> Option<TestNode<'_, H>> {
+        self.tree
+            .render()
+            .unwrap_or_else(|err| panic!("render failed: {}", err))
+    }
+
+    /// Every mounted host element matching `predicate`, in pre-order.
+    pub fn find_elements<P>(&self, predicate: P) -> Vec<&H>
+    where
+        P: Fn(&H) -> bool,
+    {
+        self.tree
+            .inspect()
+            .into_iter()
+            .filter_map(|node| node.element)
+            .filter(|element| predicate(element))
+            .collect()
+    }
+
+    /// The first mounted host element matching `predicate`.
+    ///
+    /// Panics if there isn't one.
+    pub fn find_element<P>(&self, predicate: P) -> &H
+    where
+        P: Fn(&H) -> bool,
+    {
+        self.find_elements(predicate)
+            .into_iter()
+            .next()
+            .expect("no mounted host element matches the predicate")
+    }
+
+    /// The state of every mounted `Class`, in pre-order.
+    pub fn find_states<Class>(&self) -> Vec<&Class::State>
+    where
+        Class: Component<H> + 'static,
+    {
+        self.tree
+            .find_components::<Class>()
+            .into_iter()
+            .map(|handle| handle.state())
+            .collect()
+    }
+
+    /// The state of the only mounted `Class`.
+    ///
+    /// Panics unless exactly one is mounted.
+    pub fn state<Class>(&self) -> &Class::State
+    where
+        Class: Component<H> + 'static,
+    {
+        let mut states = self.find_states::<Class>();
+        if states.len() != 1 {
+            panic!(
+                "expected one mounted {}, found {}",
+                type_name::<Class>(),
+                states.len()
+            );
+        }
+        states.remove(0)
+    }
+
+    /// Updates the state of the only mounted `Class` and flushes.
+    ///
+    /// Panics unless exactly one is mounted.
+    pub fn set_state<Class, Func>(&mut self, func: Func)
+    where
+        Class: Component<H> + 'static,
+        Func: FnOnce(Class::State) -> Class::State + 'static,
+    {
+        let updater = {
+            let handles = self.tree.find_components::<Class>();
+            if handles.len() != 1 {
+                panic!(
+                    "expected one mounted {}, found {}",
+                    type_name::<Class>(),
+                    handles.len()
+                );
+            }
+            handles[0].updater()
+        };
+        updater.set_state(func);
+        self.flush();
+    }
+
+    /// Calls `fire` with the first host element matching `predicate`,
+    /// then flushes any updates it scheduled. Use this to invoke the
+    /// callbacks stored in host elements.
+    ///
+    /// Panics if no element matches.
+    pub fn fire<P, F>(&mut self, predicate: P, fire: F)
+    where
+        P: Fn(&H) -> bool,
+        F: FnOnce(&H),
+    {
+        fire(self.find_element(predicate));
+        self.flush();
+    }
+}
+
+impl<H> TestRenderer<H>
+where
+    H: HostElement + fmt::Debug,
+{
+    /// See `snapshot::rendered_to_text`.
+    pub fn to_text(&self) -> String {
+        snapshot::rendered_to_text(&self.tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestRenderer;
+    use component::{Component, RenderContext};
+    use element::{Element, HostElement};
+    use std::fmt;
+    use std::rc::Rc;
+
+    enum Widget {
+        Label(String),
+        Button(Rc<dyn Fn()>),
+    }
+
+    impl PartialEq for Widget {
+        fn eq(&self, other: &Self) -> bool {
+            match (self, other) {
+                (Widget::Label(a), Widget::Label(b)) => a == b,
+                // Callbacks never compare equal.
+                _ => false,
+            }
+        }
+    }
+
+    impl fmt::Debug for Widget {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Widget::Label(ref text) => write!(f, "Label {:?}", text),
+                Widget::Button(_) => write!(f, "Button"),
+            }
+        }
+    }
+
+    impl HostElement for Widget {}
+
+    fn is_button(widget: &Widget) -> bool {
+        matches!(*widget, Widget::Button(_))
+    }
+
+    fn press(widget: &Widget) {
+        if let Widget::Button(ref on_press) = *widget {
+            on_press();
+        }
+    }
+
+    struct Counter;
+
+    impl Component<Widget> for Counter {
+        type Props = ();
+        type State = u32;
+
+        fn create(_props: &()) -> (Self, u32) {
+            (Counter, 0)
+        }
+
+        fn render(&self, ctx: RenderContext<Widget, Self>) -> Element<Widget> {
+            let updater = ctx.updater;
+            let on_press = move || updater.set_state(|count| count + 1);
+            Element::new_host(
+                Widget::Label(format!("count {}", ctx.state)),
+                vec![Element::new_host(Widget::Button(Rc::new(on_press)), vec![])],
+            )
+        }
+    }
+
+    #[test]
+    fn fire_flushes_the_updates_it_schedules() {
+        let mut renderer = TestRenderer::mount(Element::new_stateful::<Counter>(()));
+        assert_eq!(renderer.to_text(), "Label \"count 0\"\n  Button\n");
+
+        renderer.fire(is_button, press);
+        renderer.fire(is_button, press);
+        assert_eq!(*renderer.state::<Counter>(), 2);
+        assert_eq!(renderer.to_text(), "Label \"count 2\"\n  Button\n");
+    }
+
+    #[test]
+    fn set_state_flushes() {
+        let mut renderer = TestRenderer::mount(Element::new_stateful::<Counter>(()));
+        renderer.set_state::<Counter, _>(|count| count + 5);
+        assert_eq!(*renderer.state::<Counter>(), 5);
+        assert_eq!(renderer.to_text(), "Label \"count 5\"\n  Button\n");
+    }
+
+    #[test]
+    fn finds_mounted_elements_and_states() {
+        let renderer = TestRenderer::mount(Element::new_fragment(vec![
+            Element::new_stateful::<Counter>(()),
+            Element::new_stateful::<Counter>(()),
+        ]));
+        assert_eq!(renderer.find_elements(is_button).len(), 2);
+        assert_eq!(renderer.find_states::<Counter>(), vec![&0, &0]);
+    }
+
+    #[test]
+    fn searches_the_rendered_tree() {
+        let renderer = TestRenderer::mount(Element::new_stateful::<Counter>(()));
+        let rendered = renderer.rendered().unwrap();
+        assert!(rendered.find(is_button).is_some());
+        assert_eq!(rendered.find_all(|_| true).len(), 2);
+        assert!(rendered.children[0]
+            .find(|widget| !is_button(widget))
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected one mounted")]
+    fn state_requires_a_single_instance() {
+        let renderer = TestRenderer::mount(Element::new_fragment(vec![
+            Element::new_stateful::<Counter>(()),
+            Element::new_stateful::<Counter>(()),
+        ]));
+        renderer.state::<Counter>();
+    }
+}
@@ -0,0 +1,137 @@
+//! The `Builder` derive, used from outside the crate like an
+//! application would.
+
+extern crate react_rs;
+
+use react_rs::testing::TestRenderer;
+use react_rs::{Builder, Component, Element, HostElement, RenderContext};
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(default)]
+struct TextLabelElt {
+    text: String,
+    font: Vec<u8>,
+}
+
+impl Default for TextLabelElt {
+    fn default() -> TextLabelElt {
+        TextLabelElt {
+            text: "label".to_owned(),
+            font: vec![1, 2],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Builder)]
+struct ListBoxElt {
+    #[builder(required)]
+    padding: f32,
+    #[builder(default = "list".to_owned())]
+    name: String,
+}
+
+#[derive(Debug, PartialEq, HostElement)]
+enum Widget {
+    TextLabel(TextLabelElt),
+    ListBox(ListBoxElt),
+}
+
+#[derive(Clone, Debug, PartialEq, Builder)]
+#[builder(component = Greeting)]
+struct GreetingProps {
+    #[builder(required)]
+    name: String,
+    #[builder(default = "!".to_owned())]
+    punctuation: String,
+}
+
+struct Greeting;
+
+impl Component<Widget> for Greeting {
+    type Props = GreetingProps;
+    type State = ();
+
+    fn render(&self, ctx: RenderContext<Widget, Greeting>) -> Element<Widget> {
+        TextLabelElt::builder()
+            .text(format!("hello {}{}", ctx.props.name, ctx.props.punctuation))
+            .into_element()
+    }
+
+    fn create(_props: &GreetingProps) -> (Greeting, ()) {
+        (Greeting, ())
+    }
+}
+
+#[test]
+fn unset_fields_take_the_struct_defaults() {
+    assert_eq!(TextLabelElt::builder().build(), TextLabelElt::default());
+    assert_eq!(
+        TextLabelElt::builder().text("hi").build(),
+        TextLabelElt {
+            text: "hi".to_owned(),
+            font: vec![1, 2],
+        }
+    );
+    assert_eq!(
+        TextLabelElt::builder().font(vec![3]).build(),
+        TextLabelElt {
+            text: "label".to_owned(),
+            font: vec![3],
+        }
+    );
+}
+
+#[test]
+fn required_fields_are_parameters() {
+    assert_eq!(
+        ListBoxElt::builder(4.0).build(),
+        ListBoxElt {
+            padding: 4.0,
+            name: "list".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn builders_become_elements() {
+    let renderer: TestRenderer<Widget> = TestRenderer::mount(
+        ListBoxElt::builder(4.0)
+            .child(TextLabelElt::builder().text("a"))
+            .children(vec![TextLabelElt::builder().text("b")])
+            .into_element(),
+    );
+    assert_eq!(
+        renderer.to_text(),
+        concat!(
+            "ListBox(ListBoxElt { padding: 4.0, name: \"list\" })\n",
+            "  TextLabel(TextLabelElt { text: \"a\", font: [1, 2] })\n",
+            "  TextLabel(TextLabelElt { text: \"b\", font: [1, 2] })\n",
+        )
+    );
+}
+
+#[test]
+fn component_builders_build_props_and_elements() {
+    assert_eq!(
+        GreetingProps::builder("you").build(),
+        GreetingProps {
+            name: "you".to_owned(),
+            punctuation: "!".to_owned(),
+        }
+    );
+
+    let renderer: TestRenderer<Widget> = TestRenderer::mount(
+        ListBoxElt::builder(0.0)
+            .child(GreetingProps::builder("a"))
+            .child(GreetingProps::builder("b").punctuation("?").into_element())
+            .into_element(),
+    );
+    assert_eq!(
+        renderer.to_text(),
+        concat!(
+            "ListBox(ListBoxElt { padding: 0.0, name: \"list\" })\n",
+            "  TextLabel(TextLabelElt { text: \"hello a!\", font: [1, 2] })\n",
+            "  TextLabel(TextLabelElt { text: \"hello b?\", font: [1, 2] })\n",
+        )
+    );
+}
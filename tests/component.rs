@@ -0,0 +1,99 @@
+//! The `#[component]` attribute, used from outside the crate like an
+//! application would.
+
+extern crate react_rs;
+
+use react_rs::testing::TestRenderer;
+use react_rs::{component, Element, HostElement, RenderContext, StateUpdater};
+use std::cell::RefCell;
+
+#[derive(Debug, PartialEq)]
+struct Label(String);
+
+impl HostElement for Label {}
+
+fn label(text: String) -> Element<Label> {
+    Element::new_host(Label(text), vec![])
+}
+
+thread_local! {
+    static LOG: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(entry: &'static str) {
+    LOG.with(|log| log.borrow_mut().push(entry));
+}
+
+fn take_log() -> Vec<&'static str> {
+    LOG.with(|log| log.borrow_mut().drain(..).collect())
+}
+
+/// Counts up by `step` until it reaches 3.
+#[component(Label)]
+fn Counter(step: &u32, count: &u32, updater: StateUpdater<Label, Counter>) -> Element<Label> {
+    fn did_mount() {
+        log("Counter mounted");
+    }
+
+    if *count < 3 {
+        let step = *step;
+        updater.set_state(move |count| count + step);
+    }
+    label(format!("count {}", count))
+}
+
+#[component(Label)]
+fn Title() -> Element<Label> {
+    label("title".to_owned())
+}
+
+#[derive(Default)]
+struct Toggle {
+    updates: u32,
+}
+
+#[component(Label, props = String, state = bool)]
+impl Toggle {
+    fn render(&self, ctx: RenderContext<Label, Toggle>) -> Element<Label> {
+        match *ctx.state {
+            true => label(format!("{} ({})", ctx.props.to_uppercase(), self.updates)),
+            false => label(ctx.props.clone()),
+        }
+    }
+
+    fn did_update(&mut self) {
+        self.updates += 1;
+    }
+
+    fn will_unmount(&mut self) {
+        log("Toggle unmounted");
+    }
+}
+
+#[test]
+fn function_components_take_props_state_and_updater() {
+    let mut renderer = TestRenderer::mount(Element::new_stateful::<Counter>(2));
+    assert_eq!(renderer.to_text(), "Label(\"count 0\")\n");
+    assert_eq!(take_log(), vec!["Counter mounted"]);
+
+    renderer.flush();
+    assert_eq!(renderer.to_text(), "Label(\"count 4\")\n");
+    assert_eq!(*renderer.state::<Counter>(), 4);
+
+    let renderer = TestRenderer::mount(Element::new_stateful::<Title>(()));
+    assert_eq!(renderer.to_text(), "Label(\"title\")\n");
+}
+
+#[test]
+fn impl_blocks_become_components() {
+    let mut renderer = TestRenderer::mount(Element::new_stateful::<Toggle>("on".to_owned()));
+    assert_eq!(renderer.to_text(), "Label(\"on\")\n");
+
+    renderer.set_state::<Toggle, _>(|_| true);
+    assert_eq!(renderer.to_text(), "Label(\"ON (0)\")\n");
+    renderer.update(Element::new_stateful::<Toggle>("off".to_owned()));
+    assert_eq!(renderer.to_text(), "Label(\"OFF (1)\")\n");
+
+    renderer.unmount();
+    assert_eq!(take_log(), vec!["Toggle unmounted"]);
+}
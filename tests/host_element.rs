@@ -0,0 +1,74 @@
+//! The `HostElement` derive, used from outside the crate like an
+//! application would.
+
+extern crate react_rs;
+
+use react_rs::testing::{TestNode, TestRenderer};
+use react_rs::{Element, HostElement};
+
+#[derive(Debug, PartialEq)]
+struct TextLabelElt {
+    text: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+struct ListBoxElt {
+    padding: u32,
+}
+
+#[derive(Debug, PartialEq, HostElement)]
+enum Widget {
+    TextLabel(TextLabelElt),
+    ListBox(ListBoxElt),
+}
+
+#[derive(Debug, PartialEq)]
+struct SlotElt<T>(T);
+
+/// Host elements can be generic over what they carry.
+#[derive(Debug, PartialEq, HostElement)]
+enum Slotted<T: 'static + PartialEq> {
+    Label(TextLabelElt),
+    Slot(SlotElt<T>),
+}
+
+fn is_host_element<H: HostElement>() {}
+
+#[test]
+fn element_structs_convert_into_the_enum() {
+    is_host_element::<Widget>();
+    assert_eq!(
+        Widget::from(TextLabelElt { text: "a" }),
+        Widget::TextLabel(TextLabelElt { text: "a" })
+    );
+    let widget: Widget = ListBoxElt { padding: 2 }.into();
+    assert_eq!(widget, Widget::ListBox(ListBoxElt { padding: 2 }));
+
+    is_host_element::<Slotted<u8>>();
+    assert_eq!(Slotted::from(SlotElt(7)), Slotted::Slot(SlotElt(7)));
+}
+
+#[test]
+fn derived_elements_mount_and_render() {
+    let renderer: TestRenderer<Widget> = TestRenderer::mount(Element::new_host(
+        ListBoxElt { padding: 4 },
+        vec![
+            Element::new_host(TextLabelElt { text: "a" }, vec![]),
+            Element::new_host(TextLabelElt { text: "b" }, vec![]),
+        ],
+    ));
+    let root: TestNode<Widget> = renderer.rendered().unwrap();
+    assert_eq!(*root.element, Widget::ListBox(ListBoxElt { padding: 4 }));
+    let children = root
+        .children
+        .iter()
+        .map(|child| child.element)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        children,
+        vec![
+            &Widget::TextLabel(TextLabelElt { text: "a" }),
+            &Widget::TextLabel(TextLabelElt { text: "b" }),
+        ]
+    );
+}
@@ -0,0 +1,72 @@
+//! `install_panic_hook`, in its own test binary since it replaces the
+//! process-wide panic hook.
+
+extern crate react_rs;
+
+use react_rs::{
+    install_panic_hook, Component, ComponentStack, Element, HostElement, RenderContext, VirtualTree,
+};
+use std::any;
+use std::cell::RefCell;
+use std::panic;
+
+#[derive(Debug, PartialEq)]
+struct Widget;
+
+impl HostElement for Widget {}
+
+struct Outer;
+
+impl Component<Widget> for Outer {
+    type Props = ();
+    type State = ();
+
+    fn render(&self, _ctx: RenderContext<Widget, Outer>) -> Element<Widget> {
+        Element::new_stateful::<Inner>(())
+    }
+
+    fn create(_props: &()) -> (Outer, ()) {
+        (Outer, ())
+    }
+}
+
+struct Inner;
+
+impl Component<Widget> for Inner {
+    type Props = ();
+    type State = ();
+
+    fn render(&self, _ctx: RenderContext<Widget, Inner>) -> Element<Widget> {
+        panic!("render panicked");
+    }
+
+    fn create(_props: &()) -> (Inner, ()) {
+        (Inner, ())
+    }
+}
+
+thread_local! {
+    static PANIC_STACK: RefCell<Option<ComponentStack>> = const { RefCell::new(None) };
+}
+
+#[test]
+fn panic_hook_sees_the_panicking_component() {
+    // The hook that `install_panic_hook` wraps records what it would
+    // print instead.
+    let original = panic::take_hook();
+    panic::set_hook(Box::new(|_| {
+        PANIC_STACK.with(|stack| *stack.borrow_mut() = Some(ComponentStack::current()));
+    }));
+    install_panic_hook();
+    let result = panic::catch_unwind(|| VirtualTree::mount(Element::new_stateful::<Outer>(())));
+    panic::set_hook(original);
+
+    assert!(result.is_err());
+    let stack = PANIC_STACK.with(|stack| stack.borrow_mut().take()).unwrap();
+    assert_eq!(
+        stack.components(),
+        [any::type_name::<Outer>(), any::type_name::<Inner>()]
+    );
+    // The stack is unwound along with the reconciler.
+    assert!(ComponentStack::current().is_empty());
+}